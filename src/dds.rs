@@ -0,0 +1,319 @@
+use std::{fs, io};
+
+use crate::tga::{TGAColor, TGAFormat, TGAImage};
+
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_ALPHA: u32 = 0x2;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDPF_LUMINANCE: u32 = 0x20000;
+
+#[derive(Clone, Copy, PartialEq)]
+enum DDSFormat {
+    BC1,
+    BC2,
+    BC3,
+    Uncompressed {
+        bits_per_pixel: u32,
+        masks: [u32; 4],
+    },
+}
+
+fn bad_dds(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("DDS: {}", reason))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> io::Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| bad_dds("truncated header"))
+}
+
+// returns every stored mip level of the first surface, largest first and top row first
+pub fn read_dds_file(filename: &str) -> io::Result<Vec<TGAImage>> {
+    read_dds(&fs::read(filename)?)
+}
+
+pub fn read_dds(data: &[u8]) -> io::Result<Vec<TGAImage>> {
+    if data.len() < 128 || &data[0..4] != b"DDS " {
+        return Err(bad_dds("missing magic"));
+    }
+    if u32_at(data, 4)? != 124 {
+        return Err(bad_dds("bad header size"));
+    }
+    let flags = u32_at(data, 8)?;
+    let height = u32_at(data, 12)? as usize;
+    let width = u32_at(data, 16)? as usize;
+    let mip_count = if flags & DDSD_MIPMAPCOUNT != 0 {
+        u32_at(data, 28)?.max(1) as usize
+    } else {
+        1
+    };
+    if width == 0 || height == 0 {
+        return Err(bad_dds("empty image"));
+    }
+
+    // pixel format starts at 76
+    let pf_flags = u32_at(data, 80)?;
+    let four_cc = &data[84..88];
+    let mut offset: usize = 128;
+    let format = if pf_flags & DDPF_FOURCC != 0 {
+        match four_cc {
+            b"DXT1" => DDSFormat::BC1,
+            b"DXT2" | b"DXT3" => DDSFormat::BC2,
+            b"DXT4" | b"DXT5" => DDSFormat::BC3,
+            b"DX10" => {
+                let dxgi_format = u32_at(data, 128)?;
+                offset += 20;
+                match dxgi_format {
+                    70..=72 => DDSFormat::BC1,
+                    73..=75 => DDSFormat::BC2,
+                    76..=78 => DDSFormat::BC3,
+                    27..=29 => DDSFormat::Uncompressed {
+                        bits_per_pixel: 32,
+                        masks: [0x0000_00FF, 0x0000_FF00, 0x00FF_0000, 0xFF00_0000],
+                    },
+                    87 | 90 | 91 => DDSFormat::Uncompressed {
+                        bits_per_pixel: 32,
+                        masks: [0x00FF_0000, 0x0000_FF00, 0x0000_00FF, 0xFF00_0000],
+                    },
+                    88 | 92 | 93 => DDSFormat::Uncompressed {
+                        bits_per_pixel: 32,
+                        masks: [0x00FF_0000, 0x0000_FF00, 0x0000_00FF, 0],
+                    },
+                    _ => return Err(bad_dds("unsupported DXGI format")),
+                }
+            }
+            _ => return Err(bad_dds("unsupported FourCC")),
+        }
+    } else if pf_flags & (DDPF_RGB | DDPF_LUMINANCE | DDPF_ALPHA) != 0 {
+        let bits_per_pixel = u32_at(data, 88)?;
+        if !matches!(bits_per_pixel, 8 | 16 | 24 | 32) {
+            return Err(bad_dds("unsupported bit count"));
+        }
+        let alpha_mask = if pf_flags & (DDPF_ALPHAPIXELS | DDPF_ALPHA) != 0 {
+            u32_at(data, 104)?
+        } else {
+            0
+        };
+        let mut masks = [
+            u32_at(data, 92)?,
+            u32_at(data, 96)?,
+            u32_at(data, 100)?,
+            alpha_mask,
+        ];
+        if pf_flags & DDPF_LUMINANCE != 0 {
+            masks[1] = masks[0];
+            masks[2] = masks[0];
+        } else if pf_flags & DDPF_RGB == 0 {
+            // alpha only
+            masks[0] = 0;
+            masks[1] = 0;
+            masks[2] = 0;
+        }
+        DDSFormat::Uncompressed {
+            bits_per_pixel,
+            masks,
+        }
+    } else {
+        return Err(bad_dds("unknown pixel format"));
+    };
+
+    let has_alpha = match format {
+        DDSFormat::BC1 | DDSFormat::BC2 | DDSFormat::BC3 => true,
+        DDSFormat::Uncompressed { masks, .. } => masks[3] != 0,
+    };
+    let image_format = if has_alpha {
+        TGAFormat::RGBA
+    } else {
+        TGAFormat::RGB
+    };
+
+    let mut levels = vec![];
+    for level in 0..mip_count {
+        let w = width.checked_shr(level as u32).unwrap_or(0).max(1);
+        let h = height.checked_shr(level as u32).unwrap_or(0).max(1);
+        // the sizes come from the header, a malformed one must not overflow
+        let blocks = w.div_ceil(4).checked_mul(h.div_ceil(4));
+        let size = match format {
+            DDSFormat::BC1 => blocks.and_then(|n| n.checked_mul(8)),
+            DDSFormat::BC2 | DDSFormat::BC3 => blocks.and_then(|n| n.checked_mul(16)),
+            DDSFormat::Uncompressed { bits_per_pixel, .. } => w
+                .checked_mul(bits_per_pixel as usize)
+                .and_then(|bits| bits.div_ceil(8).checked_mul(h)),
+        }
+        .ok_or_else(|| bad_dds("image too large"))?;
+        let Some(level_data) = offset
+            .checked_add(size)
+            .and_then(|end| data.get(offset..end))
+        else {
+            if level == 0 {
+                return Err(bad_dds("truncated image data"));
+            }
+            // some writers announce more levels than they store
+            break;
+        };
+        let mut image = TGAImage::new(w, h, image_format);
+        match format {
+            DDSFormat::Uncompressed {
+                bits_per_pixel,
+                masks,
+            } => decode_uncompressed(level_data, bits_per_pixel, &masks, &mut image),
+            _ => decode_blocks(level_data, format, &mut image),
+        }
+        levels.push(image);
+        offset += size;
+    }
+    Ok(levels)
+}
+
+fn extract_channel(pixel: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+    let value = (pixel & mask) >> mask.trailing_zeros();
+    let max = mask >> mask.trailing_zeros();
+    ((value as u64 * 255 + max as u64 / 2) / max as u64) as u8
+}
+
+fn decode_uncompressed(data: &[u8], bits_per_pixel: u32, masks: &[u32; 4], image: &mut TGAImage) {
+    let bytespp = bits_per_pixel as usize / 8;
+    let pitch = (image.get_width() * bits_per_pixel as usize).div_ceil(8);
+    for y in 0..image.get_height() {
+        for x in 0..image.get_width() {
+            let offset = y * pitch + x * bytespp;
+            let mut pixel: u32 = 0;
+            for (i, byte) in data[offset..offset + bytespp].iter().enumerate() {
+                pixel |= (*byte as u32) << (i * 8);
+            }
+            image.set(
+                x,
+                y,
+                TGAColor {
+                    r: extract_channel(pixel, masks[0]),
+                    g: extract_channel(pixel, masks[1]),
+                    b: extract_channel(pixel, masks[2]),
+                    a: if masks[3] == 0 {
+                        255
+                    } else {
+                        extract_channel(pixel, masks[3])
+                    },
+                },
+            );
+        }
+    }
+}
+
+fn rgb565(value: u16) -> [u32; 3] {
+    let r = ((value >> 11) & 0x1F) as u32;
+    let g = ((value >> 5) & 0x3F) as u32;
+    let b = (value & 0x1F) as u32;
+    [
+        (r << 3) | (r >> 2),
+        (g << 2) | (g >> 4),
+        (b << 3) | (b >> 2),
+    ]
+}
+
+fn mix(a: [u32; 3], b: [u32; 3], wa: u32, wb: u32) -> [u32; 3] {
+    let total = wa + wb;
+    [
+        (a[0] * wa + b[0] * wb) / total,
+        (a[1] * wa + b[1] * wb) / total,
+        (a[2] * wa + b[2] * wb) / total,
+    ]
+}
+
+// decodes the 8-byte color part shared by BC1-BC3 into 16 texels
+fn decode_color_block(block: &[u8], three_color_mode: bool) -> [TGAColor; 16] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let e0 = rgb565(c0);
+    let e1 = rgb565(c1);
+    let mut palette = [[0u32; 4]; 4];
+    palette[0] = [e0[0], e0[1], e0[2], 255];
+    palette[1] = [e1[0], e1[1], e1[2], 255];
+    if c0 > c1 || !three_color_mode {
+        let p2 = mix(e0, e1, 2, 1);
+        let p3 = mix(e0, e1, 1, 2);
+        palette[2] = [p2[0], p2[1], p2[2], 255];
+        palette[3] = [p3[0], p3[1], p3[2], 255];
+    } else {
+        let p2 = mix(e0, e1, 1, 1);
+        palette[2] = [p2[0], p2[1], p2[2], 255];
+        palette[3] = [0, 0, 0, 0];
+    }
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    let mut texels = [TGAColor::CLEAR; 16];
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let p = palette[((indices >> (i * 2)) & 0x3) as usize];
+        *texel = TGAColor {
+            r: p[0] as u8,
+            g: p[1] as u8,
+            b: p[2] as u8,
+            a: p[3] as u8,
+        };
+    }
+    texels
+}
+
+fn decode_bc3_alpha(block: &[u8], texels: &mut [TGAColor; 16]) {
+    let a0 = block[0] as u32;
+    let a1 = block[1] as u32;
+    let mut palette = [0u32; 8];
+    palette[0] = a0;
+    palette[1] = a1;
+    if a0 > a1 {
+        for (i, alpha) in palette.iter_mut().enumerate().skip(2) {
+            let w = i as u32 - 1;
+            *alpha = (a0 * (7 - w) + a1 * w) / 7;
+        }
+    } else {
+        for (i, alpha) in palette.iter_mut().enumerate().take(6).skip(2) {
+            let w = i as u32 - 1;
+            *alpha = (a0 * (5 - w) + a1 * w) / 5;
+        }
+        palette[6] = 0;
+        palette[7] = 255;
+    }
+    let mut indices: u64 = 0;
+    for (i, byte) in block[2..8].iter().enumerate() {
+        indices |= (*byte as u64) << (i * 8);
+    }
+    for (i, texel) in texels.iter_mut().enumerate() {
+        texel.a = palette[((indices >> (i * 3)) & 0x7) as usize] as u8;
+    }
+}
+
+fn decode_blocks(data: &[u8], format: DDSFormat, image: &mut TGAImage) {
+    let block_size = if format == DDSFormat::BC1 { 8 } else { 16 };
+    let blocks_x = image.get_width().div_ceil(4);
+    let blocks_y = image.get_height().div_ceil(4);
+    for by in 0..blocks_y {
+        for bx in 0..blocks_x {
+            let offset = (bx + by * blocks_x) * block_size;
+            let block = &data[offset..offset + block_size];
+            let texels = match format {
+                DDSFormat::BC1 => decode_color_block(block, true),
+                DDSFormat::BC2 => {
+                    let mut texels = decode_color_block(&block[8..], false);
+                    for (i, texel) in texels.iter_mut().enumerate() {
+                        let nibble = (block[i / 2] >> ((i % 2) * 4)) & 0xF;
+                        texel.a = nibble * 17;
+                    }
+                    texels
+                }
+                _ => {
+                    let mut texels = decode_color_block(&block[8..], false);
+                    decode_bc3_alpha(&block[0..8], &mut texels);
+                    texels
+                }
+            };
+            for (i, texel) in texels.iter().enumerate() {
+                // set ignores texels beyond the border of non multiple of 4 sizes
+                image.set(bx * 4 + i % 4, by * 4 + i / 4, *texel);
+            }
+        }
+    }
+}
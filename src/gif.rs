@@ -0,0 +1,369 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+use crate::tga::{TGAColor, TGAImage};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum GifPalette {
+    // one palette quantized from all frames, stored once in the header
+    Global,
+    // every frame gets its own quantized palette
+    PerFrame,
+}
+
+pub struct GifOptions {
+    // frame delay in hundredths of a second
+    pub delay: u16,
+    // `None` plays once, `Some(0)` loops forever, `Some(n)` repeats n times
+    pub repeat: Option<u16>,
+    pub palette: GifPalette,
+}
+
+impl Default for GifOptions {
+    fn default() -> Self {
+        Self {
+            delay: 4,
+            repeat: Some(0),
+            palette: GifPalette::Global,
+        }
+    }
+}
+
+// frames are stored top row first, i.e. the orientation `write_tga_file` writes
+pub fn write_gif_file(filename: &str, frames: &[TGAImage], options: &GifOptions) -> io::Result<()> {
+    let mut o = BufWriter::new(File::create(filename)?);
+    write_gif(&mut o, frames, options)?;
+    o.flush()
+}
+
+pub fn write_gif<W: Write>(o: &mut W, frames: &[TGAImage], options: &GifOptions) -> io::Result<()> {
+    if frames.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "GIF needs at least one frame",
+        ));
+    }
+    let width = frames[0].get_width();
+    let height = frames[0].get_height();
+    if width == 0 || height == 0 || width > u16::MAX as usize || height > u16::MAX as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Bad GIF dimensions",
+        ));
+    }
+    if frames
+        .iter()
+        .any(|frame| frame.get_width() != width || frame.get_height() != height)
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "All GIF frames must have the same size",
+        ));
+    }
+
+    let mut global_palette = if options.palette == GifPalette::Global {
+        Some(Palette::quantize(frames))
+    } else {
+        None
+    };
+
+    o.write_all(b"GIF89a")?;
+    o.write_all(&(width as u16).to_le_bytes())?;
+    o.write_all(&(height as u16).to_le_bytes())?;
+    if let Some(palette) = &global_palette {
+        o.write_all(&[0x80 | 0x70 | palette.size_bits(), 0, 0])?;
+        palette.write(o)?;
+    } else {
+        o.write_all(&[0x70, 0, 0])?;
+    }
+
+    if let Some(repeat) = options.repeat {
+        o.write_all(&[0x21, 0xFF, 0x0B])?;
+        o.write_all(b"NETSCAPE2.0")?;
+        o.write_all(&[0x03, 0x01])?;
+        o.write_all(&repeat.to_le_bytes())?;
+        o.write_all(&[0x00])?;
+    }
+
+    let has_global_palette = global_palette.is_some();
+    for frame in frames {
+        // graphic control extension: no disposal, no transparency
+        o.write_all(&[0x21, 0xF9, 0x04, 0x04])?;
+        o.write_all(&options.delay.to_le_bytes())?;
+        o.write_all(&[0x00, 0x00])?;
+
+        let mut local_palette;
+        let palette = match global_palette.as_mut() {
+            Some(palette) => palette,
+            None => {
+                local_palette = Palette::quantize(std::slice::from_ref(frame));
+                &mut local_palette
+            }
+        };
+
+        // image descriptor
+        o.write_all(&[0x2C, 0, 0, 0, 0])?;
+        o.write_all(&(width as u16).to_le_bytes())?;
+        o.write_all(&(height as u16).to_le_bytes())?;
+        if has_global_palette {
+            o.write_all(&[0x00])?;
+        } else {
+            o.write_all(&[0x80 | palette.size_bits()])?;
+            palette.write(o)?;
+        }
+
+        let indices = palette.map_image(frame);
+        let min_code_size = (palette.size_bits() + 1).max(2);
+        o.write_all(&[min_code_size])?;
+        write_sub_blocks(o, &lzw_encode(&indices, min_code_size))?;
+    }
+
+    o.write_all(&[0x3B])
+}
+
+fn write_sub_blocks<W: Write>(o: &mut W, data: &[u8]) -> io::Result<()> {
+    for chunk in data.chunks(255) {
+        o.write_all(&[chunk.len() as u8])?;
+        o.write_all(chunk)?;
+    }
+    o.write_all(&[0x00])
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u32,
+    nbits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u32) {
+        self.acc |= (code as u32) << self.nbits;
+        self.nbits += size;
+        while self.nbits >= 8 {
+            self.bytes.push(self.acc as u8);
+            self.acc >>= 8;
+            self.nbits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.nbits > 0 {
+            self.bytes.push(self.acc as u8);
+        }
+        self.bytes
+    }
+}
+
+fn lzw_encode(indices: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear_code: u16 = 1 << min_code_size;
+    let end_code = clear_code + 1;
+    let mut writer = BitWriter {
+        bytes: vec![],
+        acc: 0,
+        nbits: 0,
+    };
+    let mut dict: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut code_size = min_code_size as u32 + 1;
+    let mut next_code = end_code + 1;
+
+    writer.write(clear_code, code_size);
+    let mut current = indices[0] as u16;
+    for &index in &indices[1..] {
+        if let Some(&code) = dict.get(&(current, index)) {
+            current = code;
+            continue;
+        }
+        writer.write(current, code_size);
+        dict.insert((current, index), next_code);
+        if next_code >= (1 << code_size) && code_size < 12 {
+            code_size += 1;
+        }
+        next_code += 1;
+        if next_code == 4096 {
+            writer.write(clear_code, code_size);
+            dict.clear();
+            code_size = min_code_size as u32 + 1;
+            next_code = end_code + 1;
+        }
+        current = index as u16;
+    }
+    writer.write(current, code_size);
+    writer.write(end_code, code_size);
+    writer.finish()
+}
+
+struct Palette {
+    colors: Vec<TGAColor>,
+    // nearest palette entry for every 5-bit-per-channel color, filled lazily
+    cache: Vec<i16>,
+}
+
+// a box of the 5-bit color cube for median cut quantization
+struct ColorBox {
+    bins: Vec<(usize, u32)>,
+}
+
+fn bin_channel(bin: usize, channel: usize) -> usize {
+    (bin >> (10 - channel * 5)) & 0x1F
+}
+
+impl ColorBox {
+    fn range(&self, channel: usize) -> usize {
+        let mut lo = 31;
+        let mut hi = 0;
+        for &(bin, _) in &self.bins {
+            lo = lo.min(bin_channel(bin, channel));
+            hi = hi.max(bin_channel(bin, channel));
+        }
+        hi.saturating_sub(lo)
+    }
+
+    fn widest_channel(&self) -> (usize, usize) {
+        (0..3)
+            .map(|channel| (channel, self.range(channel)))
+            .max_by_key(|(_, range)| *range)
+            .unwrap_or((0, 0))
+    }
+
+    fn split(mut self) -> (ColorBox, ColorBox) {
+        let (channel, _) = self.widest_channel();
+        self.bins.sort_by_key(|&(bin, _)| bin_channel(bin, channel));
+        let total: u64 = self.bins.iter().map(|&(_, cnt)| cnt as u64).sum();
+        let mut acc = 0;
+        let mut cut = 1;
+        for (i, &(_, cnt)) in self.bins.iter().enumerate() {
+            acc += cnt as u64;
+            if acc * 2 >= total {
+                cut = (i + 1).clamp(1, self.bins.len() - 1);
+                break;
+            }
+        }
+        let upper = self.bins.split_off(cut);
+        (self, ColorBox { bins: upper })
+    }
+
+    fn average(&self, sums: &[[u64; 3]]) -> TGAColor {
+        let mut total = [0u64; 3];
+        let mut count = 0u64;
+        for &(bin, cnt) in &self.bins {
+            for (c, sum) in total.iter_mut().enumerate() {
+                *sum += sums[bin][c];
+            }
+            count += cnt as u64;
+        }
+        let count = count.max(1);
+        TGAColor {
+            r: (total[0] / count) as u8,
+            g: (total[1] / count) as u8,
+            b: (total[2] / count) as u8,
+            a: 255,
+        }
+    }
+}
+
+fn color_bin(color: &TGAColor) -> usize {
+    ((color.r as usize >> 3) << 10) | ((color.g as usize >> 3) << 5) | (color.b as usize >> 3)
+}
+
+impl Palette {
+    fn quantize(frames: &[TGAImage]) -> Self {
+        let mut counts = vec![0u32; 1 << 15];
+        let mut sums = vec![[0u64; 3]; 1 << 15];
+        for frame in frames {
+            for y in 0..frame.get_height() {
+                for x in 0..frame.get_width() {
+                    let color = frame.get(x, y);
+                    let bin = color_bin(&color);
+                    counts[bin] += 1;
+                    sums[bin][0] += color.r as u64;
+                    sums[bin][1] += color.g as u64;
+                    sums[bin][2] += color.b as u64;
+                }
+            }
+        }
+
+        let mut boxes = vec![ColorBox {
+            bins: counts
+                .iter()
+                .enumerate()
+                .filter(|(_, cnt)| **cnt > 0)
+                .map(|(bin, cnt)| (bin, *cnt))
+                .collect(),
+        }];
+        while boxes.len() < 256 {
+            // split the box with the most pixels among those that can still be split
+            let candidate = boxes
+                .iter()
+                .enumerate()
+                .filter(|(_, b)| b.bins.len() > 1)
+                .max_by_key(|(_, b)| {
+                    let pixels: u64 = b.bins.iter().map(|&(_, cnt)| cnt as u64).sum();
+                    pixels * (b.widest_channel().1 as u64 + 1)
+                })
+                .map(|(i, _)| i);
+            let Some(i) = candidate else {
+                break;
+            };
+            let (lower, upper) = boxes.swap_remove(i).split();
+            boxes.push(lower);
+            boxes.push(upper);
+        }
+
+        Self {
+            colors: boxes.iter().map(|b| b.average(&sums)).collect(),
+            cache: vec![-1; 1 << 15],
+        }
+    }
+
+    // palettes are stored with 2^(n+1) entries
+    fn size_bits(&self) -> u8 {
+        let mut bits = 0;
+        while (2 << bits) < self.colors.len() {
+            bits += 1;
+        }
+        bits
+    }
+
+    fn write<W: Write>(&self, o: &mut W) -> io::Result<()> {
+        let entries = 2 << self.size_bits();
+        for i in 0..entries {
+            let color = self.colors.get(i).unwrap_or(&TGAColor::BLACK);
+            o.write_all(&[color.r, color.g, color.b])?;
+        }
+        Ok(())
+    }
+
+    fn nearest(&mut self, color: &TGAColor) -> u8 {
+        let bin = color_bin(color);
+        if self.cache[bin] >= 0 {
+            return self.cache[bin] as u8;
+        }
+        let mut best = 0;
+        let mut best_dist = i32::MAX;
+        for (i, p) in self.colors.iter().enumerate() {
+            let dr = p.r as i32 - color.r as i32;
+            let dg = p.g as i32 - color.g as i32;
+            let db = p.b as i32 - color.b as i32;
+            let dist = dr * dr + dg * dg + db * db;
+            if dist < best_dist {
+                best = i;
+                best_dist = dist;
+            }
+        }
+        self.cache[bin] = best as i16;
+        best as u8
+    }
+
+    fn map_image(&mut self, image: &TGAImage) -> Vec<u8> {
+        let mut indices = Vec::with_capacity(image.get_width() * image.get_height());
+        for y in 0..image.get_height() {
+            for x in 0..image.get_width() {
+                indices.push(self.nearest(&image.get(x, y)));
+            }
+        }
+        indices
+    }
+}
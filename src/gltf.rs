@@ -0,0 +1,739 @@
+use std::{fs, io, path::Path};
+
+use nalgebra::{Matrix3, Matrix4, Quaternion, UnitQuaternion, Vector3};
+
+use crate::{
+    json::JsonValue,
+    material::Material,
+    model::{FaceInfo, Model, ModelError, Vertex},
+};
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_CHUNK_JSON: u32 = 0x4E4F_534A;
+const GLB_CHUNK_BIN: u32 = 0x004E_4942;
+
+const MODE_TRIANGLES: usize = 4;
+const MODE_TRIANGLE_STRIP: usize = 5;
+const MODE_TRIANGLE_FAN: usize = 6;
+
+fn bad_gltf(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("glTF: {}", reason))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> io::Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| bad_gltf("truncated GLB"))
+}
+
+fn decode_base64(text: &str) -> io::Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let mut bits: u32 = 0;
+    let mut bit_cnt = 0;
+    for byte in text.bytes() {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            b' ' | b'\n' | b'\r' | b'\t' => continue,
+            _ => return Err(bad_gltf("invalid base64")),
+        };
+        bits = bits << 6 | value as u32;
+        bit_cnt += 6;
+        if bit_cnt >= 8 {
+            bit_cnt -= 8;
+            out.push((bits >> bit_cnt) as u8);
+        }
+    }
+    Ok(out)
+}
+
+// relative URIs may have %20 and the like in them
+fn decode_uri(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| uri.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn component_size(component_type: usize) -> io::Result<usize> {
+    match component_type {
+        5120 | 5121 => Ok(1),
+        5122 | 5123 => Ok(2),
+        5125 | 5126 => Ok(4),
+        _ => Err(bad_gltf("unknown component type")),
+    }
+}
+
+// one component, normalized integers are mapped to 0..1 or -1..1
+fn read_component(
+    data: &[u8],
+    offset: usize,
+    component_type: usize,
+    normalized: bool,
+) -> io::Result<f64> {
+    let size = component_size(component_type)?;
+    let b = data
+        .get(offset..offset + size)
+        .ok_or_else(|| bad_gltf("accessor out of buffer range"))?;
+    let value = match component_type {
+        5120 => b[0] as i8 as f64,
+        5121 => b[0] as f64,
+        5122 => i16::from_le_bytes([b[0], b[1]]) as f64,
+        5123 => u16::from_le_bytes([b[0], b[1]]) as f64,
+        5125 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+        _ => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+    };
+    if !normalized {
+        return Ok(value);
+    }
+    Ok(match component_type {
+        5120 => (value / 127.0).max(-1.0),
+        5121 => value / 255.0,
+        5122 => (value / 32767.0).max(-1.0),
+        5123 => value / 65535.0,
+        _ => value,
+    })
+}
+
+fn components(accessor_type: &str) -> io::Result<usize> {
+    match accessor_type {
+        "SCALAR" => Ok(1),
+        "VEC2" => Ok(2),
+        "VEC3" => Ok(3),
+        "VEC4" | "MAT2" => Ok(4),
+        "MAT3" => Ok(9),
+        "MAT4" => Ok(16),
+        _ => Err(bad_gltf("unknown accessor type")),
+    }
+}
+
+fn floats<const N: usize>(value: Option<&JsonValue>, default: [f32; N]) -> [f32; N] {
+    let mut out = default;
+    if let Some(value) = value {
+        for (i, item) in value.as_array().iter().take(N).enumerate() {
+            out[i] = item.as_f64().unwrap_or(default[i] as f64) as f32;
+        }
+    }
+    out
+}
+
+struct Gltf<'a> {
+    json: JsonValue,
+    buffers: Vec<Vec<u8>>,
+    filename: &'a str,
+    dir: &'a Path,
+}
+
+impl Gltf<'_> {
+    fn list(&self, name: &str) -> &[JsonValue] {
+        self.json.get(name).map_or(&[], JsonValue::as_array)
+    }
+
+    fn item(&self, name: &str, idx: usize) -> io::Result<&JsonValue> {
+        self.list(name)
+            .get(idx)
+            .ok_or_else(|| bad_gltf(&format!("{name} index {idx} out of range")))
+    }
+
+    // slice of a buffer view and its stride, 0 when tightly packed
+    fn buffer_view(&self, idx: usize) -> io::Result<(&[u8], usize)> {
+        let view = self.item("bufferViews", idx)?;
+        let buffer = view
+            .get("buffer")
+            .and_then(JsonValue::as_usize)
+            .and_then(|buffer| self.buffers.get(buffer))
+            .ok_or_else(|| bad_gltf("buffer view without buffer"))?;
+        let offset = view
+            .get("byteOffset")
+            .and_then(JsonValue::as_usize)
+            .unwrap_or(0);
+        let length = view
+            .get("byteLength")
+            .and_then(JsonValue::as_usize)
+            .ok_or_else(|| bad_gltf("buffer view without length"))?;
+        let data = buffer
+            .get(offset..offset + length)
+            .ok_or_else(|| bad_gltf("buffer view out of buffer range"))?;
+        let stride = view
+            .get("byteStride")
+            .and_then(JsonValue::as_usize)
+            .unwrap_or(0);
+        Ok((data, stride))
+    }
+
+    // all elements of an accessor flattened, with the number of components per element
+    fn accessor(&self, idx: usize) -> io::Result<(Vec<f64>, usize)> {
+        let accessor = self.item("accessors", idx)?;
+        let count = accessor
+            .get("count")
+            .and_then(JsonValue::as_usize)
+            .ok_or_else(|| bad_gltf("accessor without count"))?;
+        let component_type = accessor
+            .get("componentType")
+            .and_then(JsonValue::as_usize)
+            .ok_or_else(|| bad_gltf("accessor without component type"))?;
+        let size = component_size(component_type)?;
+        let n = components(
+            accessor
+                .get("type")
+                .and_then(JsonValue::as_str)
+                .unwrap_or(""),
+        )?;
+        let normalized = accessor
+            .get("normalized")
+            .and_then(JsonValue::as_bool)
+            .unwrap_or(false);
+
+        // the count comes from the file, so the view must hold all elements before
+        // anything is allocated for them
+        let source = match accessor.get("bufferView").and_then(JsonValue::as_usize) {
+            Some(view) => {
+                let (data, stride) = self.buffer_view(view)?;
+                let offset = accessor
+                    .get("byteOffset")
+                    .and_then(JsonValue::as_usize)
+                    .unwrap_or(0);
+                let stride = if stride == 0 { n * size } else { stride };
+                let end = match count.checked_sub(1) {
+                    Some(last) => last
+                        .checked_mul(stride)
+                        .and_then(|start| start.checked_add(offset))
+                        .and_then(|start| start.checked_add(n * size)),
+                    None => Some(offset),
+                };
+                if end.is_none_or(|end| end > data.len()) {
+                    return Err(bad_gltf("accessor out of buffer view range"));
+                }
+                Some((data, offset, stride))
+            }
+            None => None,
+        };
+        // an accessor without buffer view is all zeros, usually with sparse values
+        let mut values = vec![
+            0.0;
+            count
+                .checked_mul(n)
+                .ok_or_else(|| bad_gltf("accessor too large"))?
+        ];
+        if let Some((data, offset, stride)) = source {
+            for i in 0..count {
+                for c in 0..n {
+                    values[i * n + c] = read_component(
+                        data,
+                        offset + i * stride + c * size,
+                        component_type,
+                        normalized,
+                    )?;
+                }
+            }
+        }
+
+        if let Some(sparse) = accessor.get("sparse") {
+            let sparse_count = sparse
+                .get("count")
+                .and_then(JsonValue::as_usize)
+                .unwrap_or(0);
+            let (Some(indices), Some(sparse_values)) =
+                (sparse.get("indices"), sparse.get("values"))
+            else {
+                return Err(bad_gltf("incomplete sparse accessor"));
+            };
+            let view_of = |value: &JsonValue| -> io::Result<(&[u8], usize)> {
+                let view = value
+                    .get("bufferView")
+                    .and_then(JsonValue::as_usize)
+                    .ok_or_else(|| bad_gltf("sparse accessor without buffer view"))?;
+                let offset = value
+                    .get("byteOffset")
+                    .and_then(JsonValue::as_usize)
+                    .unwrap_or(0);
+                Ok((self.buffer_view(view)?.0, offset))
+            };
+            let (index_data, index_offset) = view_of(indices)?;
+            let (value_data, value_offset) = view_of(sparse_values)?;
+            let index_type = indices
+                .get("componentType")
+                .and_then(JsonValue::as_usize)
+                .ok_or_else(|| bad_gltf("sparse indices without component type"))?;
+            let index_size = component_size(index_type)?;
+            for j in 0..sparse_count {
+                let target =
+                    read_component(index_data, index_offset + j * index_size, index_type, false)?
+                        as usize;
+                if target >= count {
+                    return Err(bad_gltf("sparse index out of range"));
+                }
+                for c in 0..n {
+                    values[target * n + c] = read_component(
+                        value_data,
+                        value_offset + (j * n + c) * size,
+                        component_type,
+                        normalized,
+                    )?;
+                }
+            }
+        }
+        Ok((values, n))
+    }
+
+    // textures are loaded from files, images the renderer can't use are kept as warnings
+    // of the model
+    fn texture_path(&self, model: &mut Model, texture_info: Option<&JsonValue>) -> Option<String> {
+        let texture = texture_info?.get("index")?.as_usize()?;
+        let image_idx = self
+            .list("textures")
+            .get(texture)?
+            .get("source")?
+            .as_usize()?;
+        let image = self.list("images").get(image_idx)?;
+        let mut warn = |reason: String| {
+            if !model
+                .warnings()
+                .iter()
+                .any(|warning| warning.reason == reason)
+            {
+                model.add_warning(ModelError {
+                    file: self.filename.to_string(),
+                    line: 0,
+                    kind: io::ErrorKind::Unsupported,
+                    reason,
+                });
+            }
+        };
+        // images embedded as data URIs or buffer views have no file to point at
+        let uri = match image.get("uri").and_then(JsonValue::as_str) {
+            Some(uri) if !uri.starts_with("data:") => uri,
+            _ => {
+                warn(format!(
+                    "image {image_idx} is embedded, its texture is not loaded"
+                ));
+                return None;
+            }
+        };
+        let path = decode_uri(uri);
+        let mime_type = image.get("mimeType").and_then(JsonValue::as_str);
+        if mime_type == Some("image/png") || path.to_lowercase().ends_with(".png") {
+            warn(format!(
+                "image {image_idx} `{path}` is PNG, which textures can't be read from"
+            ));
+        }
+        Some(self.dir.join(path).to_string_lossy().into_owned())
+    }
+
+    fn material(&self, model: &mut Model, material: &JsonValue) -> Material {
+        let pbr = material.get("pbrMetallicRoughness");
+        let base_color = floats(pbr.and_then(|pbr| pbr.get("baseColorFactor")), [1.0; 4]);
+        let factor = |name: &str| {
+            pbr.and_then(|pbr| pbr.get(name))
+                .and_then(JsonValue::as_f64)
+                .map_or(1.0, |value| value as f32)
+        };
+        let metallic = factor("metallicFactor");
+        let roughness = factor("roughnessFactor");
+        let diffuse = Vector3::new(base_color[0], base_color[1], base_color[2]);
+        let opaque = material
+            .get("alphaMode")
+            .and_then(JsonValue::as_str)
+            .unwrap_or("OPAQUE")
+            == "OPAQUE";
+        // metals reflect in their base color, dielectrics at about 4%
+        let specular = Vector3::repeat(0.04).lerp(&diffuse, metallic);
+        // Phong exponent with a highlight of about the same width as the GGX lobe
+        let alpha = (roughness * roughness).max(0.01);
+        Material {
+            name: material
+                .get("name")
+                .and_then(JsonValue::as_str)
+                .unwrap_or("")
+                .to_string(),
+            diffuse,
+            specular,
+            shininess: (2.0 / (alpha * alpha) - 2.0).clamp(0.0, 1000.0),
+            dissolve: if opaque { 1.0 } else { base_color[3] },
+            metallic,
+            roughness,
+            diffuse_map: self.texture_path(model, pbr.and_then(|pbr| pbr.get("baseColorTexture"))),
+            normal_map: self.texture_path(model, material.get("normalTexture")),
+            ..Default::default()
+        }
+    }
+
+    fn node_transform(node: &JsonValue) -> Matrix4<f32> {
+        if let Some(matrix) = node.get("matrix") {
+            // column major
+            return Matrix4::from_column_slice(&floats(Some(matrix), [0.0; 16]));
+        }
+        let [tx, ty, tz] = floats(node.get("translation"), [0.0; 3]);
+        let [rx, ry, rz, rw] = floats(node.get("rotation"), [0.0, 0.0, 0.0, 1.0]);
+        let [sx, sy, sz] = floats(node.get("scale"), [1.0; 3]);
+        let rotation = UnitQuaternion::from_quaternion(Quaternion::new(rw, rx, ry, rz));
+        Matrix4::new_translation(&Vector3::new(tx, ty, tz))
+            * rotation.to_homogeneous()
+            * Matrix4::new_nonuniform_scaling(&Vector3::new(sx, sy, sz))
+    }
+
+    fn add_node(
+        &self,
+        model: &mut Model,
+        idx: usize,
+        parent: &Matrix4<f32>,
+        path: &str,
+        depth: usize,
+    ) -> io::Result<()> {
+        // node graphs are trees, deeper recursion can only come from a cycle
+        if depth > self.list("nodes").len() {
+            return Err(bad_gltf("node hierarchy has a cycle"));
+        }
+        let node = self.item("nodes", idx)?;
+        let transform = parent * Self::node_transform(node);
+        let name = node
+            .get("name")
+            .and_then(JsonValue::as_str)
+            .map_or_else(|| format!("node{idx}"), str::to_string);
+        // nested nodes become objects named by their path in the hierarchy
+        let path = if path.is_empty() {
+            name
+        } else {
+            format!("{path}/{name}")
+        };
+        if let Some(mesh) = node.get("mesh").and_then(JsonValue::as_usize) {
+            model.begin_object(&path);
+            self.add_mesh(model, mesh, &transform)?;
+        }
+        for child in node.get("children").map_or(&[][..], JsonValue::as_array) {
+            let child = child
+                .as_usize()
+                .ok_or_else(|| bad_gltf("invalid child node"))?;
+            self.add_node(model, child, &transform, &path, depth + 1)?;
+        }
+        Ok(())
+    }
+
+    fn add_mesh(&self, model: &mut Model, idx: usize, transform: &Matrix4<f32>) -> io::Result<()> {
+        let mesh = self.item("meshes", idx)?;
+        let linear: Matrix3<f32> = transform.fixed_view::<3, 3>(0, 0).into();
+        let normal_matrix = linear.try_inverse().unwrap_or(linear).transpose();
+        // mirroring transforms turn the winding around
+        let mirrored = linear.determinant() < 0.0;
+
+        for primitive in mesh.get("primitives").map_or(&[][..], JsonValue::as_array) {
+            let mode = primitive
+                .get("mode")
+                .and_then(JsonValue::as_usize)
+                .unwrap_or(MODE_TRIANGLES);
+            if ![MODE_TRIANGLES, MODE_TRIANGLE_STRIP, MODE_TRIANGLE_FAN].contains(&mode) {
+                // points and lines have no faces to render
+                continue;
+            }
+            let attributes = primitive
+                .get("attributes")
+                .ok_or_else(|| bad_gltf("primitive without attributes"))?;
+            let attribute = |name: &str| -> io::Result<Option<(Vec<f64>, usize)>> {
+                attributes
+                    .get(name)
+                    .and_then(JsonValue::as_usize)
+                    .map(|accessor| self.accessor(accessor))
+                    .transpose()
+            };
+            let (positions, n) =
+                attribute("POSITION")?.ok_or_else(|| bad_gltf("primitive without positions"))?;
+            if n != 3 {
+                return Err(bad_gltf("positions are not VEC3"));
+            }
+            let count = positions.len() / 3;
+            // the other attributes need one element per position
+            let vertex_attribute = |name: &str, min_components: usize| {
+                let values = attribute(name)?;
+                match &values {
+                    Some((values, n)) if *n < min_components || values.len() != count * n => Err(
+                        bad_gltf(&format!("{name} accessor does not match the positions")),
+                    ),
+                    _ => Ok(values),
+                }
+            };
+            let normals = vertex_attribute("NORMAL", 3)?;
+            let texture_coords = vertex_attribute("TEXCOORD_0", 2)?;
+            // VEC3 or VEC4, the alpha is not used
+            let colors = vertex_attribute("COLOR_0", 3)?;
+
+            let base = model.vertex_cnt() as u32;
+            for i in 0..count {
+                let position =
+                    Vector3::new(positions[i * 3], positions[i * 3 + 1], positions[i * 3 + 2])
+                        .map(|v| v as f32);
+                let mut vertex = Vertex {
+                    position: transform.transform_point(&position.into()).coords,
+                    color: Vector3::new(1.0, 1.0, 1.0),
+                    ..Default::default()
+                };
+                if let Some((normals, n)) = &normals {
+                    let normal =
+                        Vector3::new(normals[i * n], normals[i * n + 1], normals[i * n + 2])
+                            .map(|v| v as f32);
+                    vertex.normal = (normal_matrix * normal)
+                        .try_normalize(0.0)
+                        .unwrap_or_default();
+                }
+                if let Some((texture_coords, n)) = &texture_coords {
+                    // glTF puts the uv origin at the top left, OBJ at the bottom left
+                    vertex.texture_coord = Vector3::new(
+                        texture_coords[i * n] as f32,
+                        1.0 - texture_coords[i * n + 1] as f32,
+                        0.0,
+                    );
+                }
+                if let Some((colors, n)) = &colors {
+                    vertex.color =
+                        Vector3::new(colors[i * n], colors[i * n + 1], colors[i * n + 2])
+                            .map(|v| v as f32);
+                }
+                model.add_vertex(vertex);
+            }
+            if colors.is_some() {
+                model.set_has_vertex_colors(true);
+            }
+
+            let indices: Vec<usize> = match primitive.get("indices").and_then(JsonValue::as_usize) {
+                Some(accessor) => self
+                    .accessor(accessor)?
+                    .0
+                    .iter()
+                    .map(|&idx| idx as usize)
+                    .collect(),
+                None => (0..count).collect(),
+            };
+            if indices.iter().any(|&idx| idx >= count) {
+                return Err(bad_gltf("index out of range"));
+            }
+            let triangles: Vec<[usize; 3]> = match mode {
+                MODE_TRIANGLE_STRIP => (2..indices.len())
+                    .map(|i| {
+                        // every other triangle of a strip is wound the other way
+                        if i % 2 == 0 {
+                            [indices[i - 2], indices[i - 1], indices[i]]
+                        } else {
+                            [indices[i - 1], indices[i - 2], indices[i]]
+                        }
+                    })
+                    .collect(),
+                MODE_TRIANGLE_FAN => (2..indices.len())
+                    .map(|i| [indices[0], indices[i - 1], indices[i]])
+                    .collect(),
+                _ => indices
+                    .chunks_exact(3)
+                    .map(|t| [t[0], t[1], t[2]])
+                    .collect(),
+            };
+            let material = primitive.get("material").and_then(JsonValue::as_usize);
+            if material.is_some_and(|material| material >= model.materials().len()) {
+                return Err(bad_gltf("material index out of range"));
+            }
+            let info = FaceInfo {
+                has_texture_coords: texture_coords.is_some(),
+                has_vertex_norms: normals.is_some(),
+                material,
+                ..Default::default()
+            };
+            for [a, b, c] in triangles {
+                let (b, c) = if mirrored { (c, b) } else { (b, c) };
+                model.add_polygon(&[base + a as u32, base + b as u32, base + c as u32], info);
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn read_gltf_file(filename: &str) -> io::Result<Model> {
+    read_gltf(&fs::read(filename)?, filename)
+}
+
+// .gltf or .glb data, external buffers and textures are resolved against the directory
+// of `filename`
+pub fn read_gltf(data: &[u8], filename: &str) -> io::Result<Model> {
+    let dir = Path::new(filename).parent().unwrap_or(Path::new(""));
+    let mut bin_chunk: Option<&[u8]> = None;
+    let json = if data.starts_with(GLB_MAGIC) {
+        if u32_at(data, 4)? != 2 {
+            return Err(bad_gltf("unsupported GLB version"));
+        }
+        let length = (u32_at(data, 8)? as usize).min(data.len());
+        let mut json = None;
+        let mut offset = 12;
+        while offset + 8 <= length {
+            let chunk_length = u32_at(data, offset)? as usize;
+            let chunk_type = u32_at(data, offset + 4)?;
+            let chunk = data
+                .get(offset + 8..offset + 8 + chunk_length)
+                .ok_or_else(|| bad_gltf("truncated GLB chunk"))?;
+            match chunk_type {
+                GLB_CHUNK_JSON => json = Some(JsonValue::parse(chunk)?),
+                GLB_CHUNK_BIN => bin_chunk = Some(chunk),
+                _ => {}
+            }
+            // chunks are 4 byte aligned
+            offset += 8 + chunk_length.div_ceil(4) * 4;
+        }
+        json.ok_or_else(|| bad_gltf("GLB without JSON chunk"))?
+    } else {
+        JsonValue::parse(data)?
+    };
+
+    let version = json
+        .get("asset")
+        .and_then(|asset| asset.get("version"))
+        .and_then(JsonValue::as_str)
+        .unwrap_or("");
+    if !version.starts_with("2.") {
+        return Err(bad_gltf("only glTF 2.0 is supported"));
+    }
+
+    let mut buffers = vec![];
+    for (i, buffer) in json
+        .get("buffers")
+        .map_or(&[][..], JsonValue::as_array)
+        .iter()
+        .enumerate()
+    {
+        let data = match buffer.get("uri").and_then(JsonValue::as_str) {
+            Some(uri) if uri.starts_with("data:") => {
+                let (_, base64) = uri
+                    .split_once(";base64,")
+                    .ok_or_else(|| bad_gltf("data URI is not base64"))?;
+                decode_base64(base64)?
+            }
+            Some(uri) => fs::read(dir.join(decode_uri(uri)))?,
+            // the first buffer of a GLB without uri is its binary chunk
+            None if i == 0 => bin_chunk
+                .ok_or_else(|| bad_gltf("missing GLB binary chunk"))?
+                .to_vec(),
+            None => return Err(bad_gltf("buffer without data")),
+        };
+        buffers.push(data);
+    }
+    let gltf = Gltf {
+        json,
+        buffers,
+        filename,
+        dir,
+    };
+
+    let mut model = Model::default();
+    for material in gltf.list("materials") {
+        let material = gltf.material(&mut model, material);
+        model.add_material(material);
+    }
+    let roots: Vec<usize> = match gltf
+        .json
+        .get("scene")
+        .and_then(JsonValue::as_usize)
+        .or((!gltf.list("scenes").is_empty()).then_some(0))
+    {
+        Some(scene) => gltf
+            .item("scenes", scene)?
+            .get("nodes")
+            .map_or(&[][..], JsonValue::as_array)
+            .iter()
+            .filter_map(JsonValue::as_usize)
+            .collect(),
+        // without scenes every node that is nobody's child is a root
+        None => {
+            let children: Vec<usize> = gltf
+                .list("nodes")
+                .iter()
+                .flat_map(|node| node.get("children").map_or(&[][..], JsonValue::as_array))
+                .filter_map(JsonValue::as_usize)
+                .collect();
+            (0..gltf.list("nodes").len())
+                .filter(|idx| !children.contains(idx))
+                .collect()
+        }
+    };
+    if gltf.list("nodes").is_empty() {
+        // a file with meshes only shows them untransformed
+        for idx in 0..gltf.list("meshes").len() {
+            let name = gltf.list("meshes")[idx]
+                .get("name")
+                .and_then(JsonValue::as_str)
+                .map_or_else(|| format!("mesh{idx}"), str::to_string);
+            model.begin_object(&name);
+            gltf.add_mesh(&mut model, idx, &Matrix4::identity())?;
+        }
+    }
+    for root in roots {
+        gltf.add_node(&mut model, root, &Matrix4::identity(), "", 0)?;
+    }
+    Ok(model)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a GLB of `json` with `bin` as its buffer
+    fn glb(json: &str, bin: &[u8]) -> Vec<u8> {
+        let mut json = json.as_bytes().to_vec();
+        json.resize(json.len().div_ceil(4) * 4, b' ');
+        let mut data = GLB_MAGIC.to_vec();
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&((28 + json.len() + bin.len()) as u32).to_le_bytes());
+        for (chunk_type, chunk) in [(GLB_CHUNK_JSON, &json[..]), (GLB_CHUNK_BIN, bin)] {
+            data.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+            data.extend_from_slice(&chunk_type.to_le_bytes());
+            data.extend_from_slice(chunk);
+        }
+        data
+    }
+
+    #[test]
+    fn short_normal_accessor_is_an_error() {
+        // three positions, but only one normal
+        let floats: [f32; 12] = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+        let bin: Vec<u8> = floats.iter().flat_map(|f| f.to_le_bytes()).collect();
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "buffers": [{"byteLength": 48}],
+            "bufferViews": [
+                {"buffer": 0, "byteLength": 36},
+                {"buffer": 0, "byteOffset": 36, "byteLength": 12}
+            ],
+            "accessors": [
+                {"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"},
+                {"bufferView": 1, "componentType": 5126, "count": 1, "type": "VEC3"}
+            ],
+            "meshes": [{"primitives": [{"attributes": {"POSITION": 0, "NORMAL": 1}}]}]
+        }"#;
+        let err = read_gltf(&glb(json, &bin), "short.glb").err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "glTF: NORMAL accessor does not match the positions"
+        );
+
+        // the same file with a normal for every position loads
+        let fixed = json.replace(r#""count": 1"#, r#""count": 3"#).replace(
+            r#""byteOffset": 36, "byteLength": 12"#,
+            r#""byteLength": 36"#,
+        );
+        let model = read_gltf(&glb(&fixed, &bin), "fixed.glb").unwrap();
+        assert_eq!(model.face_cnt(), 1);
+    }
+}
@@ -0,0 +1,280 @@
+use std::{
+    fs::read_to_string,
+    io::{self},
+};
+
+use nalgebra::Vector3;
+
+use crate::tga::{TGAColor, TGAImage};
+
+pub struct Histogram {
+    pub r: [u32; 256],
+    pub g: [u32; 256],
+    pub b: [u32; 256],
+    pub a: [u32; 256],
+}
+
+impl Histogram {
+    pub fn total(&self) -> u32 {
+        self.r.iter().sum()
+    }
+
+    // returns the (low, high) channel values with `clip` of the pixels cut off at each end
+    pub fn clipped_range(channel: &[u32; 256], clip: f32) -> (u8, u8) {
+        let total: u32 = channel.iter().sum();
+        let cut = (total as f32 * clip.clamp(0.0, 0.5)) as u32;
+        let mut low = 0;
+        let mut acc = 0;
+        for (i, cnt) in channel.iter().enumerate() {
+            acc += cnt;
+            if acc > cut {
+                low = i;
+                break;
+            }
+        }
+        let mut high = 255;
+        acc = 0;
+        for (i, cnt) in channel.iter().enumerate().rev() {
+            acc += cnt;
+            if acc > cut {
+                high = i;
+                break;
+            }
+        }
+        (low as u8, high as u8)
+    }
+}
+
+fn stretch(value: u8, low: u8, high: u8) -> u8 {
+    if high <= low {
+        return value;
+    }
+    let t = (value as f32 - low as f32) / (high as f32 - low as f32);
+    (t.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+impl TGAImage {
+    pub fn histogram(&self) -> Histogram {
+        let mut histogram = Histogram {
+            r: [0; 256],
+            g: [0; 256],
+            b: [0; 256],
+            a: [0; 256],
+        };
+        for y in 0..self.get_height() {
+            for x in 0..self.get_width() {
+                let color = self.get(x, y);
+                histogram.r[color.r as usize] += 1;
+                histogram.g[color.g as usize] += 1;
+                histogram.b[color.b as usize] += 1;
+                histogram.a[color.a as usize] += 1;
+            }
+        }
+        histogram
+    }
+
+    // stretches every channel independently, which also removes color casts
+    pub fn auto_levels(&mut self, clip: f32) {
+        let histogram = self.histogram();
+        let (rl, rh) = Histogram::clipped_range(&histogram.r, clip);
+        let (gl, gh) = Histogram::clipped_range(&histogram.g, clip);
+        let (bl, bh) = Histogram::clipped_range(&histogram.b, clip);
+        self.map_colors(|color| TGAColor {
+            r: stretch(color.r, rl, rh),
+            g: stretch(color.g, gl, gh),
+            b: stretch(color.b, bl, bh),
+            a: color.a,
+        });
+    }
+
+    // stretches all channels by the same range, keeping the hues of the image
+    pub fn contrast_stretch(&mut self, clip: f32) {
+        let histogram = self.histogram();
+        let mut combined = [0; 256];
+        for (i, cnt) in combined.iter_mut().enumerate() {
+            *cnt = histogram.r[i] + histogram.g[i] + histogram.b[i];
+        }
+        let (low, high) = Histogram::clipped_range(&combined, clip);
+        self.map_colors(|color| TGAColor {
+            r: stretch(color.r, low, high),
+            g: stretch(color.g, low, high),
+            b: stretch(color.b, low, high),
+            a: color.a,
+        });
+    }
+
+    pub fn apply_lut(&mut self, lut: &Lut3D) {
+        self.map_colors(|color| lut.apply(color));
+    }
+
+    fn map_colors<F: Fn(TGAColor) -> TGAColor>(&mut self, f: F) {
+        for y in 0..self.get_height() {
+            for x in 0..self.get_width() {
+                let color = self.get(x, y);
+                self.set(x, y, f(color));
+            }
+        }
+    }
+}
+
+// 3D color lookup table as stored in Adobe / Resolve `.cube` files
+pub struct Lut3D {
+    title: String,
+    size: usize,
+    domain_min: Vector3<f32>,
+    domain_max: Vector3<f32>,
+    // red changes fastest, then green, then blue
+    table: Vec<Vector3<f32>>,
+}
+
+fn bad_cube(line_no: usize, reason: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("cube line {}: {}", line_no + 1, reason),
+    )
+}
+
+fn parse_triple<'a, I: Iterator<Item = &'a str>>(parts: I) -> Option<Vector3<f32>> {
+    let values: Vec<f32> = parts.filter_map(|fstr| fstr.parse::<f32>().ok()).collect();
+    if values.len() != 3 {
+        return None;
+    }
+    Some(Vector3::new(values[0], values[1], values[2]))
+}
+
+impl Lut3D {
+    pub fn read_cube_file(filename: &str) -> io::Result<Self> {
+        let mut lut = Lut3D {
+            title: String::new(),
+            size: 0,
+            domain_min: Vector3::zeros(),
+            domain_max: Vector3::new(1.0, 1.0, 1.0),
+            table: vec![],
+        };
+
+        for (line_no, line) in read_to_string(filename)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.split_whitespace();
+            let keyword = parts.next().unwrap_or_default();
+            match keyword {
+                "TITLE" => {
+                    lut.title = line["TITLE".len()..].trim().trim_matches('"').to_owned();
+                }
+                "LUT_3D_SIZE" => {
+                    lut.size = parts
+                        .next()
+                        .and_then(|istr| istr.parse::<usize>().ok())
+                        .filter(|size| *size >= 2)
+                        .ok_or_else(|| bad_cube(line_no, "invalid LUT_3D_SIZE"))?;
+                }
+                "LUT_1D_SIZE" => {
+                    return Err(bad_cube(line_no, "1D LUTs are not supported"));
+                }
+                "DOMAIN_MIN" => {
+                    lut.domain_min = parse_triple(parts)
+                        .ok_or_else(|| bad_cube(line_no, "invalid DOMAIN_MIN"))?;
+                }
+                "DOMAIN_MAX" => {
+                    lut.domain_max = parse_triple(parts)
+                        .ok_or_else(|| bad_cube(line_no, "invalid DOMAIN_MAX"))?;
+                }
+                "LUT_3D_INPUT_RANGE" => {
+                    let range: Vec<f32> =
+                        parts.filter_map(|fstr| fstr.parse::<f32>().ok()).collect();
+                    if range.len() != 2 {
+                        return Err(bad_cube(line_no, "invalid LUT_3D_INPUT_RANGE"));
+                    }
+                    lut.domain_min = Vector3::new(range[0], range[0], range[0]);
+                    lut.domain_max = Vector3::new(range[1], range[1], range[1]);
+                }
+                _ => {
+                    if keyword.parse::<f32>().is_err() {
+                        // unknown keywords are allowed by the spec
+                        continue;
+                    }
+                    let entry = parse_triple(line.split_whitespace())
+                        .ok_or_else(|| bad_cube(line_no, "invalid table entry"))?;
+                    lut.table.push(entry);
+                }
+            }
+        }
+
+        if lut.size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "cube file has no LUT_3D_SIZE",
+            ));
+        }
+        if lut.table.len() != lut.size * lut.size * lut.size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "cube file has {} entries, expected {}",
+                    lut.table.len(),
+                    lut.size * lut.size * lut.size
+                ),
+            ));
+        }
+        Ok(lut)
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    fn entry(&self, r: usize, g: usize, b: usize) -> &Vector3<f32> {
+        &self.table[r + self.size * (g + self.size * b)]
+    }
+
+    // trilinear lookup of a color given in the domain of the LUT
+    pub fn sample(&self, color: &Vector3<f32>) -> Vector3<f32> {
+        let n = (self.size - 1) as f32;
+        let mut base = [0; 3];
+        let mut frac = [0.0; 3];
+        for i in 0..3 {
+            let range = self.domain_max[i] - self.domain_min[i];
+            let t = if range > 0.0 {
+                (color[i] - self.domain_min[i]) / range
+            } else {
+                0.0
+            };
+            let p = t.clamp(0.0, 1.0) * n;
+            base[i] = (p.floor() as usize).min(self.size - 2);
+            frac[i] = p - base[i] as f32;
+        }
+        let [r, g, b] = base;
+        let [fr, fg, fb] = frac;
+        let c00 = self.entry(r, g, b).lerp(self.entry(r + 1, g, b), fr);
+        let c10 = self
+            .entry(r, g + 1, b)
+            .lerp(self.entry(r + 1, g + 1, b), fr);
+        let c01 = self
+            .entry(r, g, b + 1)
+            .lerp(self.entry(r + 1, g, b + 1), fr);
+        let c11 = self
+            .entry(r, g + 1, b + 1)
+            .lerp(self.entry(r + 1, g + 1, b + 1), fr);
+        let c0 = c00.lerp(&c10, fg);
+        let c1 = c01.lerp(&c11, fg);
+        c0.lerp(&c1, fb)
+    }
+
+    pub fn apply(&self, color: TGAColor) -> TGAColor {
+        let input = Vector3::new(color.r as f32, color.g as f32, color.b as f32) / 255.0;
+        let input = self.domain_min + input.component_mul(&(self.domain_max - self.domain_min));
+        let output = self.sample(&input) * 255.0;
+        TGAColor {
+            r: output.x.round().clamp(0.0, 255.0) as u8,
+            g: output.y.round().clamp(0.0, 255.0) as u8,
+            b: output.z.round().clamp(0.0, 255.0) as u8,
+            a: color.a,
+        }
+    }
+}
@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+
+use nalgebra::Vector3;
+
+use crate::model::{position_key, Model};
+
+// the half-edges of face f are 3f, 3f + 1 and 3f + 2, in the winding order of the face
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HalfEdge {
+    // welded vertex the half-edge starts at, it ends at the origin of `next`
+    pub origin: u32,
+    // the opposite half-edge of the neighboring face, None on boundary and non-manifold
+    // edges
+    pub twin: Option<u32>,
+}
+
+// adjacency of the faces of a `Model`. Vertices with the same position are welded, so
+// texture and normal seams are not boundaries. Faces that collapse to a line or point
+// after welding have half-edges, but no edges.
+pub struct HalfEdgeMesh {
+    half_edges: Vec<HalfEdge>,
+    positions: Vec<Vector3<f32>>,
+    // model vertex -> welded vertex
+    vertex_map: Vec<u32>,
+    // outgoing half-edges of every vertex, vertex v owns
+    // vertex_half_edges[vertex_starts[v]..vertex_starts[v + 1]]
+    vertex_starts: Vec<u32>,
+    vertex_half_edges: Vec<u32>,
+    // undirected edges as sorted vertex pairs, in ascending order, with their half-edges
+    // in edge_half_edges[edge_starts[e]..edge_starts[e + 1]]
+    edges: Vec<[u32; 2]>,
+    edge_starts: Vec<u32>,
+    edge_half_edges: Vec<u32>,
+}
+
+impl HalfEdgeMesh {
+    pub fn new(model: &Model) -> Self {
+        let mut positions: Vec<Vector3<f32>> = vec![];
+        let mut lookup: HashMap<[u32; 3], u32> = HashMap::new();
+        let vertex_map: Vec<u32> = model
+            .vertices()
+            .iter()
+            .map(|vertex| {
+                let p = vertex.position;
+                *lookup.entry(position_key(&p)).or_insert_with(|| {
+                    positions.push(p);
+                    (positions.len() - 1) as u32
+                })
+            })
+            .collect();
+
+        let half_edges: Vec<HalfEdge> = model
+            .indices()
+            .iter()
+            .map(|&idx| HalfEdge {
+                origin: vertex_map[idx as usize],
+                twin: None,
+            })
+            .collect();
+        let mut mesh = Self {
+            half_edges,
+            positions,
+            vertex_map,
+            vertex_starts: vec![],
+            vertex_half_edges: vec![],
+            edges: vec![],
+            edge_starts: vec![],
+            edge_half_edges: vec![],
+        };
+
+        // group the half-edges of every undirected edge by sorting on the vertex pair
+        let mut sorted: Vec<(u32, u32, u32)> = (0..mesh.half_edges.len() as u32)
+            .filter(|&h| !mesh.is_degenerate(h / 3))
+            .map(|h| {
+                let [a, b] = mesh.half_edge_vertices(h);
+                (a.min(b), a.max(b), h)
+            })
+            .collect();
+        sorted.sort_unstable();
+        for (i, &(a, b, h)) in sorted.iter().enumerate() {
+            if i == 0 || mesh.edges.last() != Some(&[a, b]) {
+                mesh.edges.push([a, b]);
+                mesh.edge_starts.push(i as u32);
+            }
+            mesh.edge_half_edges.push(h);
+        }
+        mesh.edge_starts.push(sorted.len() as u32);
+
+        // only an edge with two faces of opposite winding joins them
+        for e in 0..mesh.edges.len() {
+            if let &[h0, h1] = mesh.edge_half_edges(e) {
+                if mesh.half_edges[h0 as usize].origin != mesh.half_edges[h1 as usize].origin {
+                    mesh.half_edges[h0 as usize].twin = Some(h1);
+                    mesh.half_edges[h1 as usize].twin = Some(h0);
+                }
+            }
+        }
+
+        let mut outgoing: Vec<(u32, u32)> = (0..mesh.half_edges.len() as u32)
+            .filter(|&h| !mesh.is_degenerate(h / 3))
+            .map(|h| (mesh.half_edges[h as usize].origin, h))
+            .collect();
+        outgoing.sort_unstable();
+        mesh.vertex_starts = vec![0; mesh.positions.len() + 1];
+        for &(v, _) in &outgoing {
+            mesh.vertex_starts[v as usize + 1] += 1;
+        }
+        for v in 0..mesh.positions.len() {
+            mesh.vertex_starts[v + 1] += mesh.vertex_starts[v];
+        }
+        mesh.vertex_half_edges = outgoing.into_iter().map(|(_, h)| h).collect();
+        mesh
+    }
+
+    pub fn vertex_cnt(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn position(&self, vertex: u32) -> Vector3<f32> {
+        self.positions[vertex as usize]
+    }
+
+    // the welded vertex of a vertex of the model
+    pub fn model_vertex(&self, idx: u32) -> u32 {
+        self.vertex_map[idx as usize]
+    }
+
+    pub fn face_cnt(&self) -> usize {
+        self.half_edges.len() / 3
+    }
+
+    pub fn face(&self, face: u32) -> [u32; 3] {
+        let h = face as usize * 3;
+        [
+            self.half_edges[h].origin,
+            self.half_edges[h + 1].origin,
+            self.half_edges[h + 2].origin,
+        ]
+    }
+
+    // a face with two corners at the same position
+    pub fn is_degenerate(&self, face: u32) -> bool {
+        let [a, b, c] = self.face(face);
+        a == b || b == c || c == a
+    }
+
+    pub fn half_edge_cnt(&self) -> usize {
+        self.half_edges.len()
+    }
+
+    pub fn half_edge(&self, h: u32) -> &HalfEdge {
+        &self.half_edges[h as usize]
+    }
+
+    pub fn next(&self, h: u32) -> u32 {
+        h / 3 * 3 + (h + 1) % 3
+    }
+
+    pub fn prev(&self, h: u32) -> u32 {
+        h / 3 * 3 + (h + 2) % 3
+    }
+
+    pub fn half_edge_face(&self, h: u32) -> u32 {
+        h / 3
+    }
+
+    // start and end vertex
+    pub fn half_edge_vertices(&self, h: u32) -> [u32; 2] {
+        [
+            self.half_edges[h as usize].origin,
+            self.half_edges[self.next(h) as usize].origin,
+        ]
+    }
+
+    pub fn edge_cnt(&self) -> usize {
+        self.edges.len()
+    }
+
+    // the two vertices of an edge, smaller index first
+    pub fn edge(&self, edge: usize) -> [u32; 2] {
+        self.edges[edge]
+    }
+
+    pub fn find_edge(&self, a: u32, b: u32) -> Option<usize> {
+        self.edges.binary_search(&[a.min(b), a.max(b)]).ok()
+    }
+
+    pub fn edge_half_edges(&self, edge: usize) -> &[u32] {
+        &self.edge_half_edges[self.edge_starts[edge] as usize..self.edge_starts[edge + 1] as usize]
+    }
+
+    // faces sharing the edge, one on a boundary and more than two on a non-manifold edge
+    pub fn edge_faces(&self, edge: usize) -> impl Iterator<Item = u32> + '_ {
+        self.edge_half_edges(edge).iter().map(|&h| h / 3)
+    }
+
+    pub fn is_boundary_edge(&self, edge: usize) -> bool {
+        self.edge_half_edges(edge).len() == 1
+    }
+
+    // an edge of more than two faces, or of two faces whose winding disagrees
+    pub fn is_non_manifold_edge(&self, edge: usize) -> bool {
+        match self.edge_half_edges(edge) {
+            [_] => false,
+            &[h0, _] => self.half_edges[h0 as usize].twin.is_none(),
+            _ => true,
+        }
+    }
+
+    pub fn boundary_edges(&self) -> Vec<usize> {
+        (0..self.edge_cnt())
+            .filter(|&edge| self.is_boundary_edge(edge))
+            .collect()
+    }
+
+    pub fn non_manifold_edges(&self) -> Vec<usize> {
+        (0..self.edge_cnt())
+            .filter(|&edge| self.is_non_manifold_edge(edge))
+            .collect()
+    }
+
+    // the faces across the three edges of the face, None where there is no single
+    // neighbor
+    pub fn face_neighbors(&self, face: u32) -> [Option<u32>; 3] {
+        [0, 1, 2].map(|i| self.half_edges[(face * 3 + i) as usize].twin.map(|h| h / 3))
+    }
+
+    pub fn vertex_half_edges(&self, vertex: u32) -> &[u32] {
+        let v = vertex as usize;
+        &self.vertex_half_edges[self.vertex_starts[v] as usize..self.vertex_starts[v + 1] as usize]
+    }
+
+    pub fn vertex_faces(&self, vertex: u32) -> impl Iterator<Item = u32> + '_ {
+        self.vertex_half_edges(vertex).iter().map(|&h| h / 3)
+    }
+
+    // vertices sharing an edge with the vertex, unordered
+    pub fn one_ring(&self, vertex: u32) -> Vec<u32> {
+        let mut ring: Vec<u32> = vec![];
+        for &h in self.vertex_half_edges(vertex) {
+            // the end of the outgoing edge and the start of the incoming one of the face
+            for neighbor in [
+                self.half_edges[self.next(h) as usize].origin,
+                self.half_edges[self.prev(h) as usize].origin,
+            ] {
+                if !ring.contains(&neighbor) {
+                    ring.push(neighbor);
+                }
+            }
+        }
+        ring
+    }
+
+    // on an edge without a twin, either a boundary or a non-manifold one
+    pub fn is_boundary_vertex(&self, vertex: u32) -> bool {
+        self.vertex_half_edges(vertex).iter().any(|&h| {
+            self.half_edges[h as usize].twin.is_none()
+                || self.half_edges[self.prev(h) as usize].twin.is_none()
+        })
+    }
+
+    // closed loops of boundary edges as vertex lists, in the winding order of the faces
+    // along them. A vertex where several boundaries touch may appear twice in a loop.
+    pub fn holes(&self) -> Vec<Vec<u32>> {
+        let mut outgoing: HashMap<u32, Vec<u32>> = HashMap::new();
+        for edge in self.boundary_edges() {
+            let h = self.edge_half_edges(edge)[0];
+            outgoing
+                .entry(self.half_edges[h as usize].origin)
+                .or_default()
+                .push(h);
+        }
+        let mut starts: Vec<u32> = outgoing.keys().copied().collect();
+        starts.sort_unstable();
+
+        let mut holes = vec![];
+        for start in starts {
+            while let Some(h) = outgoing.get_mut(&start).and_then(Vec::pop) {
+                let mut hole = vec![start];
+                let mut vertex = self.half_edge_vertices(h)[1];
+                while vertex != start {
+                    hole.push(vertex);
+                    match outgoing.get_mut(&vertex).and_then(Vec::pop) {
+                        Some(h) => vertex = self.half_edge_vertices(h)[1],
+                        // an open chain, only possible around non-manifold edges
+                        None => break,
+                    }
+                }
+                if vertex == start {
+                    holes.push(hole);
+                }
+            }
+        }
+        holes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::ParseMode;
+
+    #[test]
+    fn negative_zero_is_welded() {
+        // a unit quad split along its diagonal, the second triangle writes the seam with -0
+        let obj = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv -0 -0 0\nv 1 1 -0\nv 0 1 0\nf 1 2 3\nf 4 5 6\n";
+        let model = Model::from_reader(obj.as_bytes(), ParseMode::Strict).unwrap();
+        let mesh = HalfEdgeMesh::new(&model);
+        assert_eq!(mesh.vertex_cnt(), 4);
+        assert_eq!(mesh.edge_cnt(), 5);
+        assert_eq!(mesh.boundary_edges().len(), 4);
+        assert!(mesh.non_manifold_edges().is_empty());
+        assert_eq!(mesh.face_neighbors(0)[2], Some(1));
+        let holes = mesh.holes();
+        assert_eq!(holes.len(), 1);
+        assert_eq!(holes[0].len(), 4);
+    }
+}
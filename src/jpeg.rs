@@ -0,0 +1,583 @@
+use std::{f32::consts::PI, fs, io};
+
+use crate::tga::{TGAColor, TGAFormat, TGAImage};
+
+#[rustfmt::skip]
+const ZIGZAG: [usize; 64] = [
+     0,  1,  8, 16,  9,  2,  3, 10,
+    17, 24, 32, 25, 18, 11,  4,  5,
+    12, 19, 26, 33, 40, 48, 41, 34,
+    27, 20, 13,  6,  7, 14, 21, 28,
+    35, 42, 49, 56, 57, 50, 43, 36,
+    29, 22, 15, 23, 30, 37, 44, 51,
+    58, 59, 52, 45, 38, 31, 39, 46,
+    53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+fn bad_jpeg(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("JPEG: {}", reason))
+}
+
+#[derive(Clone, Default)]
+struct HuffmanTable {
+    // canonical decoding tables as in section F.2.2.3 of the spec
+    max_code: [i32; 17],
+    val_offset: [i32; 17],
+    values: Vec<u8>,
+}
+
+impl HuffmanTable {
+    fn new(counts: &[u8; 16], values: Vec<u8>) -> Self {
+        let mut table = HuffmanTable {
+            max_code: [-1; 17],
+            val_offset: [0; 17],
+            values,
+        };
+        let mut code: i32 = 0;
+        let mut k: i32 = 0;
+        for len in 1..=16 {
+            let cnt = counts[len - 1] as i32;
+            if cnt > 0 {
+                table.val_offset[len] = k - code;
+                code += cnt;
+                k += cnt;
+                table.max_code[len] = code - 1;
+            }
+            code <<= 1;
+        }
+        table
+    }
+}
+
+#[derive(Clone, Default)]
+struct Component {
+    id: u8,
+    h: usize,
+    v: usize,
+    quant_table: usize,
+    dc_table: usize,
+    ac_table: usize,
+    dc_pred: i32,
+    // samples of all blocks, padded to whole MCUs
+    plane: Vec<u8>,
+    plane_width: usize,
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    acc: u32,
+    nbits: u32,
+}
+
+impl BitReader<'_> {
+    fn fill(&mut self) {
+        while self.nbits <= 24 {
+            let mut byte = 0;
+            if self.pos < self.data.len() {
+                byte = self.data[self.pos];
+                if byte == 0xFF {
+                    let next = self.data.get(self.pos + 1).copied().unwrap_or(0);
+                    if next == 0x00 {
+                        self.pos += 2;
+                    } else {
+                        // a marker, keep feeding zeros until it is consumed explicitly
+                        byte = 0;
+                    }
+                } else {
+                    self.pos += 1;
+                }
+            }
+            self.acc |= (byte as u32) << (24 - self.nbits);
+            self.nbits += 8;
+        }
+    }
+
+    fn bit(&mut self) -> u32 {
+        self.fill();
+        let bit = self.acc >> 31;
+        self.acc <<= 1;
+        self.nbits -= 1;
+        bit
+    }
+
+    fn bits(&mut self, count: u32) -> u32 {
+        if count == 0 {
+            return 0;
+        }
+        self.fill();
+        let value = self.acc >> (32 - count);
+        self.acc <<= count;
+        self.nbits -= count;
+        value
+    }
+
+    fn decode(&mut self, table: &HuffmanTable) -> io::Result<u8> {
+        let mut code: i32 = 0;
+        for len in 1..=16 {
+            code = (code << 1) | self.bit() as i32;
+            if code <= table.max_code[len] {
+                let idx = (code + table.val_offset[len]) as usize;
+                return table
+                    .values
+                    .get(idx)
+                    .copied()
+                    .ok_or_else(|| bad_jpeg("bad huffman code"));
+            }
+        }
+        Err(bad_jpeg("bad huffman code"))
+    }
+
+    fn receive_extend(&mut self, size: u8) -> i32 {
+        if size == 0 {
+            return 0;
+        }
+        let value = self.bits(size as u32) as i32;
+        if value < (1 << (size - 1)) {
+            value - (1 << size) + 1
+        } else {
+            value
+        }
+    }
+
+    // drops buffered bits and skips the RSTn marker that must follow
+    fn restart(&mut self) -> io::Result<()> {
+        self.acc = 0;
+        self.nbits = 0;
+        while self.pos + 1 < self.data.len() && self.data[self.pos] != 0xFF {
+            self.pos += 1;
+        }
+        match self.data.get(self.pos + 1) {
+            Some(0xD0..=0xD7) => {
+                self.pos += 2;
+                Ok(())
+            }
+            _ => Err(bad_jpeg("missing restart marker")),
+        }
+    }
+}
+
+struct Decoder {
+    width: usize,
+    height: usize,
+    components: Vec<Component>,
+    quant_tables: [[u16; 64]; 4],
+    dc_tables: [HuffmanTable; 4],
+    ac_tables: [HuffmanTable; 4],
+    restart_interval: usize,
+    h_max: usize,
+    v_max: usize,
+    adobe_transform: Option<u8>,
+    idct_table: [[f32; 8]; 8],
+}
+
+fn u16_at(data: &[u8], offset: usize) -> io::Result<usize> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]) as usize)
+        .ok_or_else(|| bad_jpeg("truncated segment"))
+}
+
+pub fn read_jpeg_file(filename: &str) -> io::Result<TGAImage> {
+    read_jpeg(&fs::read(filename)?)
+}
+
+// decodes a baseline (sequential huffman) JPEG, top row first
+pub fn read_jpeg(data: &[u8]) -> io::Result<TGAImage> {
+    if data.len() < 4 || data[0] != 0xFF || data[1] != 0xD8 {
+        return Err(bad_jpeg("missing SOI marker"));
+    }
+    let mut idct_table = [[0.0; 8]; 8];
+    for (x, row) in idct_table.iter_mut().enumerate() {
+        for (u, value) in row.iter_mut().enumerate() {
+            let cu = if u == 0 { 1.0 / 2f32.sqrt() } else { 1.0 };
+            *value = cu * ((2 * x + 1) as f32 * u as f32 * PI / 16.0).cos() / 2.0;
+        }
+    }
+    let mut decoder = Decoder {
+        width: 0,
+        height: 0,
+        components: vec![],
+        quant_tables: [[0; 64]; 4],
+        dc_tables: Default::default(),
+        ac_tables: Default::default(),
+        restart_interval: 0,
+        h_max: 1,
+        v_max: 1,
+        adobe_transform: None,
+        idct_table,
+    };
+
+    let mut pos = 2;
+    loop {
+        // skip fill bytes before the marker
+        while pos < data.len() && data[pos] == 0xFF && data.get(pos + 1) == Some(&0xFF) {
+            pos += 1;
+        }
+        if pos + 1 >= data.len() {
+            // tolerate a missing EOI marker
+            break;
+        }
+        if data[pos] != 0xFF {
+            return Err(bad_jpeg("expected a marker"));
+        }
+        let marker = data[pos + 1];
+        pos += 2;
+        if marker == 0xD9 {
+            break;
+        }
+        if (0xD0..=0xD7).contains(&marker) || marker == 0x01 {
+            continue;
+        }
+        let length = u16_at(data, pos)?;
+        let segment = data
+            .get(pos + 2..pos + length)
+            .ok_or_else(|| bad_jpeg("truncated segment"))?;
+        match marker {
+            0xC0 | 0xC1 => decoder.read_frame(segment)?,
+            0xC2..=0xCF if marker != 0xC4 && marker != 0xC8 && marker != 0xCC => {
+                return Err(bad_jpeg("only baseline JPEGs are supported"));
+            }
+            0xC4 => decoder.read_huffman_tables(segment)?,
+            0xDB => decoder.read_quant_tables(segment)?,
+            0xDD => decoder.restart_interval = u16_at(segment, 0)?,
+            0xEE if segment.len() >= 12 && segment.starts_with(b"Adobe") => {
+                decoder.adobe_transform = Some(segment[11]);
+            }
+            0xDA => {
+                pos += length;
+                pos = decoder.read_scan(segment, data, pos)?;
+                continue;
+            }
+            _ => {}
+        }
+        pos += length;
+    }
+
+    decoder.to_image()
+}
+
+impl Decoder {
+    fn read_frame(&mut self, segment: &[u8]) -> io::Result<()> {
+        if segment.len() < 6 || segment[0] != 8 {
+            return Err(bad_jpeg("only 8-bit precision is supported"));
+        }
+        self.height = u16_at(segment, 1)?;
+        self.width = u16_at(segment, 3)?;
+        let count = segment[5] as usize;
+        if self.width == 0 || self.height == 0 {
+            return Err(bad_jpeg("bad dimensions"));
+        }
+        if count != 1 && count != 3 {
+            return Err(bad_jpeg(
+                "only grayscale and 3-component images are supported",
+            ));
+        }
+        for i in 0..count {
+            let spec = segment
+                .get(6 + i * 3..9 + i * 3)
+                .ok_or_else(|| bad_jpeg("truncated frame header"))?;
+            let h = (spec[1] >> 4) as usize;
+            let v = (spec[1] & 0xF) as usize;
+            if !(1..=4).contains(&h) || !(1..=4).contains(&v) || spec[2] > 3 {
+                return Err(bad_jpeg("bad component"));
+            }
+            self.components.push(Component {
+                id: spec[0],
+                h,
+                v,
+                quant_table: spec[2] as usize,
+                ..Default::default()
+            });
+        }
+        self.h_max = self.components.iter().map(|c| c.h).max().unwrap_or(1);
+        self.v_max = self.components.iter().map(|c| c.v).max().unwrap_or(1);
+        let mcus_x = self.width.div_ceil(8 * self.h_max);
+        let mcus_y = self.height.div_ceil(8 * self.v_max);
+        for component in &mut self.components {
+            component.plane_width = mcus_x * component.h * 8;
+            component.plane = vec![0; component.plane_width * mcus_y * component.v * 8];
+        }
+        Ok(())
+    }
+
+    fn read_huffman_tables(&mut self, segment: &[u8]) -> io::Result<()> {
+        let mut pos = 0;
+        while pos < segment.len() {
+            let class = segment[pos] >> 4;
+            let id = (segment[pos] & 0xF) as usize;
+            if class > 1 || id > 3 {
+                return Err(bad_jpeg("bad huffman table"));
+            }
+            let mut counts = [0u8; 16];
+            counts.copy_from_slice(
+                segment
+                    .get(pos + 1..pos + 17)
+                    .ok_or_else(|| bad_jpeg("truncated huffman table"))?,
+            );
+            let total: usize = counts.iter().map(|c| *c as usize).sum();
+            let values = segment
+                .get(pos + 17..pos + 17 + total)
+                .ok_or_else(|| bad_jpeg("truncated huffman table"))?
+                .to_vec();
+            let table = HuffmanTable::new(&counts, values);
+            if class == 0 {
+                self.dc_tables[id] = table;
+            } else {
+                self.ac_tables[id] = table;
+            }
+            pos += 17 + total;
+        }
+        Ok(())
+    }
+
+    fn read_quant_tables(&mut self, segment: &[u8]) -> io::Result<()> {
+        let mut pos = 0;
+        while pos < segment.len() {
+            let precision = segment[pos] >> 4;
+            let id = (segment[pos] & 0xF) as usize;
+            if id > 3 {
+                return Err(bad_jpeg("bad quantization table"));
+            }
+            pos += 1;
+            for (i, &zigzag) in ZIGZAG.iter().enumerate() {
+                self.quant_tables[id][zigzag] = if precision == 0 {
+                    *segment
+                        .get(pos + i)
+                        .ok_or_else(|| bad_jpeg("truncated table"))? as u16
+                } else {
+                    u16_at(segment, pos + i * 2)? as u16
+                };
+            }
+            pos += if precision == 0 { 64 } else { 128 };
+        }
+        Ok(())
+    }
+
+    // decodes the entropy coded data following the SOS segment, returns the position after it
+    fn read_scan(&mut self, segment: &[u8], data: &[u8], start: usize) -> io::Result<usize> {
+        if self.components.is_empty() {
+            return Err(bad_jpeg("scan before frame header"));
+        }
+        let count = *segment.first().ok_or_else(|| bad_jpeg("empty scan"))? as usize;
+        let mut scan_components = vec![];
+        for i in 0..count {
+            let spec = segment
+                .get(1 + i * 2..3 + i * 2)
+                .ok_or_else(|| bad_jpeg("truncated scan header"))?;
+            let idx = self
+                .components
+                .iter()
+                .position(|c| c.id == spec[0])
+                .ok_or_else(|| bad_jpeg("scan references unknown component"))?;
+            self.components[idx].dc_table = (spec[1] >> 4) as usize & 3;
+            self.components[idx].ac_table = (spec[1] & 0xF) as usize & 3;
+            self.components[idx].dc_pred = 0;
+            scan_components.push(idx);
+        }
+
+        let mut reader = BitReader {
+            data,
+            pos: start,
+            acc: 0,
+            nbits: 0,
+        };
+        // a single component scan is not interleaved, every MCU is one block
+        let (mcus_x, mcus_y) = if scan_components.len() == 1 {
+            let c = &self.components[scan_components[0]];
+            (
+                (self.width * c.h).div_ceil(self.h_max * 8),
+                (self.height * c.v).div_ceil(self.v_max * 8),
+            )
+        } else {
+            (
+                self.width.div_ceil(8 * self.h_max),
+                self.height.div_ceil(8 * self.v_max),
+            )
+        };
+
+        let mut block = [0i32; 64];
+        for mcu in 0..mcus_x * mcus_y {
+            if self.restart_interval > 0 && mcu > 0 && mcu % self.restart_interval == 0 {
+                reader.restart()?;
+                for &idx in &scan_components {
+                    self.components[idx].dc_pred = 0;
+                }
+            }
+            let mx = mcu % mcus_x;
+            let my = mcu / mcus_x;
+            for &idx in &scan_components {
+                let (h, v) = if scan_components.len() == 1 {
+                    (1, 1)
+                } else {
+                    (self.components[idx].h, self.components[idx].v)
+                };
+                for by in 0..v {
+                    for bx in 0..h {
+                        self.decode_block(&mut reader, idx, &mut block)?;
+                        self.store_block(idx, &block, (mx * h + bx) * 8, (my * v + by) * 8);
+                    }
+                }
+            }
+        }
+
+        // skip to the next marker that is not a restart marker
+        let mut pos = reader.pos;
+        while pos + 1 < data.len() {
+            if data[pos] == 0xFF && data[pos + 1] != 0x00 && !(0xD0..=0xD7).contains(&data[pos + 1])
+            {
+                break;
+            }
+            pos += 1;
+        }
+        Ok(pos)
+    }
+
+    fn decode_block(
+        &mut self,
+        reader: &mut BitReader,
+        idx: usize,
+        block: &mut [i32; 64],
+    ) -> io::Result<()> {
+        let component = &mut self.components[idx];
+        let quant = &self.quant_tables[component.quant_table];
+        block.fill(0);
+
+        let size = reader.decode(&self.dc_tables[component.dc_table])?;
+        if size > 11 {
+            return Err(bad_jpeg("bad DC coefficient"));
+        }
+        // a corrupt stream can keep adding differences, it must not overflow
+        component.dc_pred = component.dc_pred.wrapping_add(reader.receive_extend(size));
+        block[0] = component.dc_pred.wrapping_mul(quant[0] as i32);
+
+        let ac_table = &self.ac_tables[component.ac_table];
+        let mut k = 1;
+        while k < 64 {
+            let rs = reader.decode(ac_table)?;
+            let run = (rs >> 4) as usize;
+            let size = rs & 0xF;
+            if size == 0 {
+                if run == 15 {
+                    k += 16;
+                    continue;
+                }
+                break;
+            }
+            k += run;
+            if k > 63 {
+                return Err(bad_jpeg("bad AC coefficient"));
+            }
+            let pos = ZIGZAG[k];
+            block[pos] = reader.receive_extend(size) * quant[pos] as i32;
+            k += 1;
+        }
+        Ok(())
+    }
+
+    fn store_block(&mut self, idx: usize, block: &[i32; 64], x: usize, y: usize) {
+        let component = &mut self.components[idx];
+        if x + 8 > component.plane_width || (y + 8) * component.plane_width > component.plane.len()
+        {
+            return;
+        }
+        // separable float IDCT, rows then columns
+        let mut tmp = [0.0f32; 64];
+        for row in 0..8 {
+            for px in 0..8 {
+                let mut sum = 0.0;
+                for u in 0..8 {
+                    sum += self.idct_table[px][u] * block[row * 8 + u] as f32;
+                }
+                tmp[row * 8 + px] = sum;
+            }
+        }
+        for col in 0..8 {
+            for py in 0..8 {
+                let mut sum = 0.0;
+                for v in 0..8 {
+                    sum += self.idct_table[py][v] * tmp[v * 8 + col];
+                }
+                let value = (sum + 128.0).round().clamp(0.0, 255.0) as u8;
+                component.plane[(y + py) * component.plane_width + x + col] = value;
+            }
+        }
+    }
+
+    // bilinear sample of a (possibly subsampled) component at full resolution pixel (x, y)
+    fn sample(&self, component: &Component, x: usize, y: usize) -> f32 {
+        if component.h == self.h_max && component.v == self.v_max {
+            return component.plane[y * component.plane_width + x] as f32;
+        }
+        let plane_height = component.plane.len() / component.plane_width;
+        let sx = ((x as f32 + 0.5) * component.h as f32 / self.h_max as f32 - 0.5).max(0.0);
+        let sy = ((y as f32 + 0.5) * component.v as f32 / self.v_max as f32 - 0.5).max(0.0);
+        let x0 = (sx as usize).min(component.plane_width - 1);
+        let y0 = (sy as usize).min(plane_height - 1);
+        let x1 = (x0 + 1).min(component.plane_width - 1);
+        let y1 = (y0 + 1).min(plane_height - 1);
+        let fx = sx - x0 as f32;
+        let fy = sy - y0 as f32;
+        let at = |px: usize, py: usize| component.plane[py * component.plane_width + px] as f32;
+        let top = at(x0, y0) * (1.0 - fx) + at(x1, y0) * fx;
+        let bottom = at(x0, y1) * (1.0 - fx) + at(x1, y1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+
+    fn to_image(&self) -> io::Result<TGAImage> {
+        if self.components.is_empty() {
+            return Err(bad_jpeg("missing frame header"));
+        }
+        if self.components.len() == 1 {
+            // grayscale TGA images read back as blue, so the value goes into every channel
+            let mut image = TGAImage::new(self.width, self.height, TGAFormat::RGB);
+            let component = &self.components[0];
+            for y in 0..self.height {
+                for x in 0..self.width {
+                    let value = component.plane[y * component.plane_width + x];
+                    image.set(
+                        x,
+                        y,
+                        TGAColor {
+                            r: value,
+                            g: value,
+                            b: value,
+                            a: 255,
+                        },
+                    );
+                }
+            }
+            return Ok(image);
+        }
+
+        // Adobe transform 0 means the components are stored as RGB
+        let is_rgb = self.adobe_transform == Some(0);
+        let mut image = TGAImage::new(self.width, self.height, TGAFormat::RGB);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let c0 = self.sample(&self.components[0], x, y);
+                let c1 = self.sample(&self.components[1], x, y);
+                let c2 = self.sample(&self.components[2], x, y);
+                let (r, g, b) = if is_rgb {
+                    (c0, c1, c2)
+                } else {
+                    (
+                        c0 + 1.402 * (c2 - 128.0),
+                        c0 - 0.344136 * (c1 - 128.0) - 0.714136 * (c2 - 128.0),
+                        c0 + 1.772 * (c1 - 128.0),
+                    )
+                };
+                image.set(
+                    x,
+                    y,
+                    TGAColor {
+                        r: r.round().clamp(0.0, 255.0) as u8,
+                        g: g.round().clamp(0.0, 255.0) as u8,
+                        b: b.round().clamp(0.0, 255.0) as u8,
+                        a: 255,
+                    },
+                );
+            }
+        }
+        Ok(image)
+    }
+}
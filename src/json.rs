@@ -0,0 +1,251 @@
+use std::io;
+
+// nesting beyond this is rejected instead of overflowing the stack
+const MAX_DEPTH: usize = 256;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    // members in file order
+    Object(Vec<(String, JsonValue)>),
+}
+
+fn bad_json(reason: &str, pos: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("JSON: {} at byte {}", reason, pos),
+    )
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.data.len()
+            && matches!(self.data[self.pos], b' ' | b'\t' | b'\n' | b'\r')
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.data.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> io::Result<()> {
+        if self.peek() != Some(byte) {
+            return Err(bad_json(&format!("expected `{}`", byte as char), self.pos));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn literal(&mut self, text: &str, value: JsonValue) -> io::Result<JsonValue> {
+        if !self.data[self.pos..].starts_with(text.as_bytes()) {
+            return Err(bad_json("unexpected literal", self.pos));
+        }
+        self.pos += text.len();
+        Ok(value)
+    }
+
+    fn value(&mut self, depth: usize) -> io::Result<JsonValue> {
+        if depth > MAX_DEPTH {
+            return Err(bad_json("nested too deeply", self.pos));
+        }
+        match self.peek() {
+            Some(b'{') => {
+                self.pos += 1;
+                let mut members = vec![];
+                if self.peek() == Some(b'}') {
+                    self.pos += 1;
+                    return Ok(JsonValue::Object(members));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    self.expect(b':')?;
+                    members.push((key, self.value(depth + 1)?));
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b'}') => {
+                            self.pos += 1;
+                            return Ok(JsonValue::Object(members));
+                        }
+                        _ => return Err(bad_json("expected `,` or `}`", self.pos)),
+                    }
+                }
+            }
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = vec![];
+                if self.peek() == Some(b']') {
+                    self.pos += 1;
+                    return Ok(JsonValue::Array(items));
+                }
+                loop {
+                    items.push(self.value(depth + 1)?);
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b']') => {
+                            self.pos += 1;
+                            return Ok(JsonValue::Array(items));
+                        }
+                        _ => return Err(bad_json("expected `,` or `]`", self.pos)),
+                    }
+                }
+            }
+            Some(b'"') => Ok(JsonValue::String(self.string()?)),
+            Some(b't') => self.literal("true", JsonValue::Bool(true)),
+            Some(b'f') => self.literal("false", JsonValue::Bool(false)),
+            Some(b'n') => self.literal("null", JsonValue::Null),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(bad_json("unexpected character", self.pos)),
+            None => Err(bad_json("unexpected end", self.pos)),
+        }
+    }
+
+    fn number(&mut self) -> io::Result<JsonValue> {
+        let start = self.pos;
+        while self.pos < self.data.len()
+            && matches!(
+                self.data[self.pos],
+                b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'
+            )
+        {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.data[start..self.pos])
+            .ok()
+            .and_then(|text| text.parse::<f64>().ok())
+            .map(JsonValue::Number)
+            .ok_or_else(|| bad_json("invalid number", start))
+    }
+
+    fn hex4(&mut self) -> io::Result<u32> {
+        let hex = self
+            .data
+            .get(self.pos..self.pos + 4)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .ok_or_else(|| bad_json("invalid unicode escape", self.pos))?;
+        self.pos += 4;
+        Ok(hex)
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        if self.data.get(self.pos) != Some(&b'"') {
+            return Err(bad_json("expected string", self.pos));
+        }
+        self.pos += 1;
+        let mut bytes: Vec<u8> = vec![];
+        loop {
+            let Some(&byte) = self.data.get(self.pos) else {
+                return Err(bad_json("unterminated string", self.pos));
+            };
+            self.pos += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let Some(&escape) = self.data.get(self.pos) else {
+                        return Err(bad_json("unterminated string", self.pos));
+                    };
+                    self.pos += 1;
+                    let c = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let mut code = self.hex4()?;
+                            // a high surrogate is followed by the low one
+                            if (0xD800..0xDC00).contains(&code)
+                                && self.data[self.pos..].starts_with(b"\\u")
+                            {
+                                self.pos += 2;
+                                let low = self.hex4()?;
+                                code = 0x10000
+                                    + ((code - 0xD800) << 10)
+                                    + (low.wrapping_sub(0xDC00) & 0x3FF);
+                            }
+                            char::from_u32(code).unwrap_or('\u{FFFD}')
+                        }
+                        _ => return Err(bad_json("invalid escape", self.pos - 1)),
+                    };
+                    let mut buf = [0u8; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+                _ => bytes.push(byte),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| bad_json("string is not UTF-8", self.pos))
+    }
+}
+
+impl JsonValue {
+    pub fn parse(data: &[u8]) -> io::Result<Self> {
+        let mut parser = Parser { data, pos: 0 };
+        let value = parser.value(0)?;
+        if parser.peek().is_some() {
+            return Err(bad_json("trailing data", parser.pos));
+        }
+        Ok(value)
+    }
+
+    // member of an object, None for other values
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            JsonValue::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        self.as_f64()
+            .filter(|number| *number >= 0.0 && number.fract() == 0.0)
+            .map(|number| number as usize)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            JsonValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    // empty for anything but an array, so optional arrays can be iterated directly
+    pub fn as_array(&self) -> &[JsonValue] {
+        match self {
+            JsonValue::Array(items) => items,
+            _ => &[],
+        }
+    }
+}
@@ -1,31 +1,74 @@
-use model::Model;
-use nalgebra::{Matrix4, Matrix4x3, Vector3, Vector4};
+use dds::read_dds_file;
+use gltf::read_gltf_file;
+use jpeg::read_jpeg_file;
+use line::draw_line;
+use model::{Model, NormalWeighting};
+use nalgebra::{Matrix4, Matrix4x3, Vector2, Vector3, Vector4};
+use ply::read_ply_file;
 use std::{
     io::{self},
     time::Instant,
 };
+use stl::read_stl_file;
+use terminal::TerminalTarget;
+use text::draw_text;
 use tga::{TGAColor, TGAImage};
 use triangle::draw_triangle;
 
+pub mod dds;
+pub mod gif;
+pub mod gltf;
+pub mod grading;
+pub mod half_edge;
+pub mod jpeg;
+pub mod json;
 pub mod line;
+pub mod material;
 pub mod model;
+pub mod ply;
+pub mod stl;
+pub mod terminal;
+pub mod text;
 pub mod tga;
 pub mod triangle;
+pub mod triangulate;
+pub mod video;
 
-fn render_model(model_file: &str, texture_file: &str, image: &mut TGAImage) -> io::Result<()> {
-    let model = Model::new(model_file)?;
-    let width = image.get_width();
-    let height = image.get_height();
-
-    let light_dir = Vector3::new(0.0, 0.0, 1.0);
+fn load_texture(texture_file: &str) -> io::Result<TGAImage> {
+    let lowercase = texture_file.to_lowercase();
+    if lowercase.ends_with(".jpg") || lowercase.ends_with(".jpeg") {
+        return read_jpeg_file(texture_file);
+    }
+    if lowercase.ends_with(".dds") {
+        // only the full resolution level is used for now
+        return read_dds_file(texture_file)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "DDS has no levels"));
+    }
+    let mut texture = TGAImage::new(0, 0, tga::TGAFormat::RGB);
+    texture.read_tga_file(texture_file)?;
+    Ok(texture)
+}
 
-    let mut z_buffer: Vec<f32> = vec![f32::MIN; width * height];
-    // read texture
-    let mut texture = TGAImage::new(width, height, tga::TGAFormat::RGB);
-    texture.read_tga_file(texture_file);
-    texture.flip_vertically();
-    texture.scale(width, height);
+fn load_model(model_file: &str) -> io::Result<Model> {
+    let lowercase = model_file.to_lowercase();
+    let model = if lowercase.ends_with(".gltf") || lowercase.ends_with(".glb") {
+        read_gltf_file(model_file)?
+    } else if lowercase.ends_with(".ply") {
+        read_ply_file(model_file)?
+    } else if lowercase.ends_with(".stl") {
+        read_stl_file(model_file)?
+    } else {
+        Model::new(model_file)?
+    };
+    for warning in model.warnings() {
+        eprintln!("[tinyrenderer] warning: {warning}");
+    }
+    Ok(model)
+}
 
+fn screen_transform(width: usize, height: usize) -> Matrix4<f32> {
     let depth = 255.0;
     #[rustfmt::skip]
     let vx = (width as f32) / 8.0;
@@ -57,22 +100,127 @@ fn render_model(model_file: &str, texture_file: &str, image: &mut TGAImage) -> i
         0.0, 0.0, 1.0, 0.0,
         0.0, 0.0, -1.0/3.0, 1.0,
     );
-    // render vertices
-    for i in 0..model.face_cnt() {
+    view_port * projection
+}
+
+// faces of the objects and groups named in `hidden`
+fn hidden_faces(model: &Model, hidden: &[String]) -> Vec<bool> {
+    let mut mask = vec![false; model.face_cnt()];
+    for sub_mesh in model.objects().iter().chain(model.groups()) {
+        if hidden.contains(&sub_mesh.name) {
+            mask[sub_mesh.faces.clone()].fill(true);
+        }
+    }
+    mask
+}
+
+fn render_model(
+    model_file: &str,
+    texture_file: &str,
+    hidden: &[String],
+    image: &mut TGAImage,
+) -> io::Result<usize> {
+    let mut model = load_model(model_file)?;
+    // keep edges sharper than 60 degrees for models without normals
+    model.generate_smooth_normals(NormalWeighting::Angle, Some(60f32.to_radians()));
+    let hidden_faces = hidden_faces(&model, hidden);
+    let width = image.get_width();
+    let height = image.get_height();
+
+    let light_dir = Vector3::new(0.0, 0.0, 1.0);
+
+    let mut z_buffer: Vec<f32> = vec![f32::MIN; width * height];
+    // read textures, faces without a material or without a diffuse map use the default one
+    let read_texture = |filename: &str| -> io::Result<TGAImage> {
+        let mut texture = load_texture(filename)?;
+        texture.flip_vertically();
+        texture.scale(width, height);
+        Ok(texture)
+    };
+    let texture = read_texture(texture_file)?;
+    let material_textures: Vec<Option<TGAImage>> = model
+        .materials()
+        .iter()
+        .map(|material| {
+            let diffuse_map = material.diffuse_map.as_deref()?;
+            read_texture(diffuse_map)
+                .map_err(|err| {
+                    eprintln!(
+                        "[tinyrenderer] warning: cannot read diffuse map {diffuse_map}: {err}"
+                    );
+                })
+                .ok()
+        })
+        .collect();
+
+    // models of any size and position fill the viewport
+    let transform = screen_transform(width, height) * model.normalization_transform();
+    // transform every unique vertex once
+    let screen: Vec<Vector4<f32>> = model
+        .vertices()
+        .iter()
+        .map(|vertex| transform * vertex.position.insert_row(3, 1.0))
+        .collect();
+    // render faces
+    let mut drawn = 0;
+    for (i, &hidden) in hidden_faces.iter().enumerate() {
+        if hidden {
+            continue;
+        }
+        drawn += 1;
         let face = model.face(i);
+        let info = model.face_info(i);
 
-        let mut vertices = Matrix4x3::identity();
+        let mut screen_vertices = Matrix4x3::zeros();
         let mut texture_coords: Vec<Vector3<f32>> = vec![];
         let mut vertex_norms: Vec<Vector3<f32>> = vec![];
 
-        for j in 0..3 {
-            let vertex = model.vertex(face[j].x as usize);
-            vertices.set_column(j, &vertex.insert_row(3, 1.0));
-            texture_coords.push(model.texture_coord(face[j].y as usize).to_owned());
-            vertex_norms.push(model.vertex_norm(face[j].z as usize).to_owned());
+        for (j, &idx) in face.iter().enumerate() {
+            let vertex = model.vertex(idx as usize);
+            screen_vertices.set_column(j, &screen[idx as usize]);
+            texture_coords.push(vertex.texture_coord);
+            vertex_norms.push(vertex.normal);
         }
 
-        let screen_vertices = view_port * projection * vertices;
+        // materials with a diffuse map use it, others are drawn in their diffuse color
+        let (face_texture, color, use_texture) = match info.material {
+            Some(m) => match &material_textures[m] {
+                Some(material_texture) => {
+                    (material_texture, TGAColor::WHITE, info.has_texture_coords)
+                }
+                None => {
+                    let kd = model.materials()[m]
+                        .diffuse
+                        .map(|c| (c.clamp(0.0, 1.0) * 255.0) as u8);
+                    let color = TGAColor {
+                        r: kd.x,
+                        g: kd.y,
+                        b: kd.z,
+                        a: 255,
+                    };
+                    (&texture, color, false)
+                }
+            },
+            // vertex colors replace the default texture
+            None => (
+                &texture,
+                TGAColor::WHITE,
+                info.has_texture_coords && !model.has_vertex_colors(),
+            ),
+        };
+        // vertex colors tint faces drawn without a texture
+        let colors = face.map(|idx| {
+            if !model.has_vertex_colors() {
+                return color;
+            }
+            let tint = model.vertex(idx as usize).color.map(|c| c.clamp(0.0, 1.0));
+            TGAColor {
+                r: (color.r as f32 * tint.x) as u8,
+                g: (color.g as f32 * tint.y) as u8,
+                b: (color.b as f32 * tint.z) as u8,
+                a: color.a,
+            }
+        });
 
         draw_triangle(
             &screen_vertices,
@@ -81,12 +229,43 @@ fn render_model(model_file: &str, texture_file: &str, image: &mut TGAImage) -> i
             &light_dir,
             image,
             &mut z_buffer,
-            &texture,
-            &TGAColor::WHITE,
-            true,
+            face_texture,
+            &colors,
+            use_texture,
         );
     }
-    Ok(())
+    Ok(drawn)
+}
+
+fn render_wireframe(
+    model_file: &str,
+    hidden: &[String],
+    image: &mut TGAImage,
+) -> io::Result<usize> {
+    let model = load_model(model_file)?;
+    let hidden_faces = hidden_faces(&model, hidden);
+    let transform =
+        screen_transform(image.get_width(), image.get_height()) * model.normalization_transform();
+    let to_screen = |corner: u32| {
+        let v = transform * model.vertex(corner as usize).position.insert_row(3, 1.0);
+        Vector2::new(v.x / v.w, v.y / v.w)
+    };
+    // outlines of the original polygons, without the triangulation diagonals
+    let mut drawn = 0;
+    for i in 0..model.polygon_cnt() {
+        let faces = model.polygon_faces(i);
+        if hidden_faces[faces.start] {
+            continue;
+        }
+        drawn += faces.len();
+        let corners = model.polygon(i);
+        for j in 0..corners.len() {
+            let a = to_screen(corners[j]);
+            let b = to_screen(corners[(j + 1) % corners.len()]);
+            draw_line(&a, &b, image, TGAColor::WHITE, false);
+        }
+    }
+    Ok(drawn)
 }
 
 fn main() {
@@ -94,13 +273,35 @@ fn main() {
     let width: usize = 800;
     let height: usize = 800;
     let mut image = TGAImage::new(width, height, tga::TGAFormat::RGB);
-    _ = render_model(
-        "obj/african_head.obj",
-        "obj/african_head_diffuse.tga",
-        &mut image,
-    );
+    // --hide=<name> skips an object or group of the model
+    let hidden: Vec<String> = std::env::args()
+        .filter_map(|arg| arg.strip_prefix("--hide=").map(str::to_string))
+        .collect();
+    let triangles = if std::env::args().any(|arg| arg == "--wireframe") {
+        render_wireframe("obj/african_head.obj", &hidden, &mut image)
+    } else {
+        render_model(
+            "obj/african_head.obj",
+            "obj/african_head_diffuse.tga",
+            &hidden,
+            &mut image,
+        )
+    }
+    .unwrap_or_else(|err| {
+        eprintln!("[tinyrenderer] {err}");
+        0
+    });
 
     image.flip_vertically();
+    let annotation = format!(
+        "frame 0\n{:?}\n{} triangles\ncamera z=3",
+        start.elapsed(),
+        triangles
+    );
+    draw_text(&mut image, 8, 8, &annotation, TGAColor::WHITE, 2);
     _ = image.write_tga_file("output.tga", true);
+    if std::env::args().any(|arg| arg == "--terminal") {
+        _ = TerminalTarget::detect().print(&image);
+    }
     println!("[tinyrenderer] {:?}", start.elapsed());
 }
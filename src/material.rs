@@ -0,0 +1,207 @@
+use std::{
+    fs::{read_to_string, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use nalgebra::Vector3;
+
+#[derive(Clone)]
+pub struct Material {
+    pub name: String,
+    // Ka, Kd, Ks
+    pub ambient: Vector3<f32>,
+    pub diffuse: Vector3<f32>,
+    pub specular: Vector3<f32>,
+    // Ns
+    pub shininess: f32,
+    // d, or 1 - Tr
+    pub dissolve: f32,
+    pub illum: u32,
+    // Pm and Pr of the PBR extension, or the glTF metallic-roughness factors
+    pub metallic: f32,
+    pub roughness: f32,
+    // texture paths, already resolved against the directory of the .mtl file
+    pub diffuse_map: Option<String>,
+    pub specular_map: Option<String>,
+    pub normal_map: Option<String>,
+    pub alpha_map: Option<String>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            ambient: Vector3::zeros(),
+            diffuse: Vector3::new(1.0, 1.0, 1.0),
+            specular: Vector3::zeros(),
+            shininess: 0.0,
+            dissolve: 1.0,
+            illum: 2,
+            metallic: 0.0,
+            roughness: 1.0,
+            diffuse_map: None,
+            specular_map: None,
+            normal_map: None,
+            alpha_map: None,
+        }
+    }
+}
+
+fn parse_color<'a, I: Iterator<Item = &'a str>>(parts: I) -> Option<Vector3<f32>> {
+    let values: Vec<f32> = parts.filter_map(|fstr| fstr.parse::<f32>().ok()).collect();
+    match values.len() {
+        // a single value is used for all channels
+        1 => Some(Vector3::new(values[0], values[0], values[0])),
+        3 => Some(Vector3::new(values[0], values[1], values[2])),
+        _ => None,
+    }
+}
+
+// strips options like `-bm 0.5` or `-s 1 1 1` from a map statement and returns the file name
+fn parse_map<'a, I: Iterator<Item = &'a str>>(parts: I, dir: &Path) -> Option<String> {
+    let mut name: Vec<&str> = vec![];
+    let mut in_option = false;
+    for part in parts {
+        if part.starts_with('-') && part.parse::<f32>().is_err() {
+            in_option = true;
+            continue;
+        }
+        if in_option && (part.parse::<f32>().is_ok() || part == "on" || part == "off") {
+            continue;
+        }
+        in_option = false;
+        name.push(part);
+    }
+    if name.is_empty() {
+        return None;
+    }
+    Some(dir.join(name.join(" ")).to_string_lossy().into_owned())
+}
+
+pub fn read_mtl_file(filename: &str) -> io::Result<Vec<Material>> {
+    let dir = Path::new(filename).parent().unwrap_or(Path::new(""));
+    let mut materials: Vec<Material> = vec![];
+
+    for line in read_to_string(filename)?.lines() {
+        let mut parts = line.split_whitespace();
+        let Some(keyword) = parts.next() else {
+            continue;
+        };
+        if keyword == "newmtl" {
+            materials.push(Material {
+                name: parts.collect::<Vec<&str>>().join(" "),
+                ..Default::default()
+            });
+            continue;
+        }
+        // statements before the first newmtl have nothing to apply to
+        let Some(material) = materials.last_mut() else {
+            continue;
+        };
+        match keyword {
+            "Ka" => material.ambient = parse_color(parts).unwrap_or(material.ambient),
+            "Kd" => material.diffuse = parse_color(parts).unwrap_or(material.diffuse),
+            "Ks" => material.specular = parse_color(parts).unwrap_or(material.specular),
+            "Ns" => {
+                if let Some(value) = parts.next().and_then(|fstr| fstr.parse::<f32>().ok()) {
+                    material.shininess = value;
+                }
+            }
+            "d" => {
+                // `d -halo 0.5` is treated as a plain dissolve
+                if let Some(value) = parts.filter_map(|fstr| fstr.parse::<f32>().ok()).next() {
+                    material.dissolve = value;
+                }
+            }
+            "Tr" => {
+                if let Some(value) = parts.next().and_then(|fstr| fstr.parse::<f32>().ok()) {
+                    material.dissolve = 1.0 - value;
+                }
+            }
+            "illum" => {
+                if let Some(value) = parts.next().and_then(|istr| istr.parse::<u32>().ok()) {
+                    material.illum = value;
+                }
+            }
+            "Pm" => {
+                if let Some(value) = parts.next().and_then(|fstr| fstr.parse::<f32>().ok()) {
+                    material.metallic = value;
+                }
+            }
+            "Pr" => {
+                if let Some(value) = parts.next().and_then(|fstr| fstr.parse::<f32>().ok()) {
+                    material.roughness = value;
+                }
+            }
+            "map_Kd" => material.diffuse_map = parse_map(parts, dir),
+            "map_Ks" => material.specular_map = parse_map(parts, dir),
+            "map_Bump" | "map_bump" | "bump" | "norm" => {
+                material.normal_map = parse_map(parts, dir)
+            }
+            "map_d" => material.alpha_map = parse_map(parts, dir),
+            _ => {}
+        }
+    }
+    Ok(materials)
+}
+
+pub fn write_mtl_file(filename: &str, materials: &[Material], precision: usize) -> io::Result<()> {
+    let dir = Path::new(filename).parent().unwrap_or(Path::new(""));
+    let mut out = BufWriter::new(File::create(filename)?);
+    write_mtl(&mut out, materials, precision, dir)?;
+    out.flush()
+}
+
+// texture paths below `dir` are written relative to it and all others as absolute paths,
+// so `read_mtl_file` resolves them to the same files
+pub fn write_mtl<W: Write>(
+    out: &mut W,
+    materials: &[Material],
+    precision: usize,
+    dir: &Path,
+) -> io::Result<()> {
+    for (i, material) in materials.iter().enumerate() {
+        if i > 0 {
+            writeln!(out)?;
+        }
+        writeln!(out, "newmtl {}", material.name)?;
+        for (keyword, color) in [
+            ("Ka", material.ambient),
+            ("Kd", material.diffuse),
+            ("Ks", material.specular),
+        ] {
+            writeln!(
+                out,
+                "{keyword} {:.*} {:.*} {:.*}",
+                precision, color.x, precision, color.y, precision, color.z
+            )?;
+        }
+        writeln!(out, "Ns {:.*}", precision, material.shininess)?;
+        writeln!(out, "d {:.*}", precision, material.dissolve)?;
+        writeln!(out, "illum {}", material.illum)?;
+        // the PBR statements are left out when they have their defaults, for older readers
+        let default = Material::default();
+        if material.metallic != default.metallic || material.roughness != default.roughness {
+            writeln!(out, "Pm {:.*}", precision, material.metallic)?;
+            writeln!(out, "Pr {:.*}", precision, material.roughness)?;
+        }
+        for (keyword, map) in [
+            ("map_Kd", &material.diffuse_map),
+            ("map_Ks", &material.specular_map),
+            ("map_Bump", &material.normal_map),
+            ("map_d", &material.alpha_map),
+        ] {
+            if let Some(map) = map {
+                let path = Path::new(map);
+                let path = match path.strip_prefix(dir) {
+                    Ok(relative) => relative.to_path_buf(),
+                    // relative to the working directory, which the .mtl doesn't know
+                    Err(_) => std::path::absolute(path)?,
+                };
+                writeln!(out, "{keyword} {}", path.display())?;
+            }
+        }
+    }
+    Ok(())
+}
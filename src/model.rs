@@ -1,114 +1,1957 @@
-use std::{fs::read_to_string, io};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    ops::Range,
+    path::Path,
+    str, thread,
+};
 
-use nalgebra::Vector3;
+use bytemuck::{bytes_of, bytes_of_mut, cast_slice, cast_slice_mut, pod_read_unaligned, Pod};
+use bytemuck_derive::{Pod, Zeroable};
+use nalgebra::{Matrix4, Vector3, Vector4};
 
+use crate::{
+    material::{read_mtl_file, write_mtl_file, Material},
+    triangulate::{polygon_normal, triangulate_polygon},
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+#[repr(C)]
+pub struct Vertex {
+    pub position: Vector3<f32>,
+    pub texture_coord: Vector3<f32>,
+    pub normal: Vector3<f32>,
+    pub color: Vector3<f32>,
+    // xyz tangent and handedness in w, zero until `Model::generate_tangents`
+    pub tangent: Vector4<f32>,
+}
+
+impl Vertex {
+    pub fn bitangent(&self) -> Vector3<f32> {
+        self.normal.cross(&self.tangent.xyz()) * self.tangent.w
+    }
+}
+
+// which optional attributes the face corners reference, missing ones are zero in the vertex
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct FaceInfo {
+    pub has_texture_coords: bool,
+    pub has_vertex_norms: bool,
+    // index into `Model::materials`
+    pub material: Option<usize>,
+    // `s` statement in effect, 0 when smoothing is off
+    pub smoothing_group: u32,
+}
+
+// a face of the file before triangulation, kept for wireframe display
+#[derive(Clone, Debug, PartialEq)]
+pub struct Polygon {
+    pub corners: Range<usize>,
+    pub faces: Range<usize>,
+    pub info: FaceInfo,
+}
+
+// a named part of the model from an `o` or `g` statement
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubMesh {
+    pub name: String,
+    pub faces: Range<usize>,
+}
+
+// axis-aligned bounds of the vertex positions
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingBox {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl BoundingBox {
+    pub fn center(&self) -> Vector3<f32> {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn size(&self) -> Vector3<f32> {
+        self.max - self.min
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vector3<f32>,
+    pub radius: f32,
+}
+
+// how the faces around a vertex contribute to its smooth normal
+#[derive(Clone, Copy, PartialEq)]
+pub enum NormalWeighting {
+    // by the angle of the face at the vertex, independent of tessellation
+    Angle,
+    // by the face area, large faces dominate
+    Area,
+}
+
+// hashable form of a vector, adding 0.0 turns -0.0 into 0.0, which is the same position
+pub fn position_key(position: &Vector3<f32>) -> [u32; 3] {
+    position.map(|c| (c + 0.0).to_bits()).into()
+}
+
+// an empty index means the attribute is left out
+fn parse_index(istr: &str) -> Result<Option<i64>, String> {
+    if istr.is_empty() {
+        return Ok(None);
+    }
+    istr.parse::<i64>()
+        .map(Some)
+        .map_err(|_| format!("invalid index `{istr}`"))
+}
+
+// resolves a 1-based or negative (relative to the end) OBJ index
+fn resolve_index(idx: i64, count: usize) -> Result<usize, String> {
+    if idx > 0 && idx as usize <= count {
+        Ok(idx as usize - 1)
+    } else if idx < 0 && idx.unsigned_abs() as usize <= count {
+        Ok(count - idx.unsigned_abs() as usize)
+    } else {
+        Err(format!("index {idx} out of range, {count} defined so far"))
+    }
+}
+
+// parses up to `values.len()` numbers without allocating, returns how many there were
+fn parse_floats(keyword: &str, args: &str, values: &mut [f32]) -> Result<usize, String> {
+    let mut count = 0;
+    for fstr in args.split_whitespace() {
+        let value = fstr
+            .parse::<f32>()
+            .map_err(|_| format!("invalid number `{fstr}` in `{keyword}`"))?;
+        if count < values.len() {
+            values[count] = value;
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
+// x y z, x y z w or x y z r g b; w only matters for rational curves
+fn parse_vertex(args: &str) -> Result<(Vector3<f32>, Option<Vector3<f32>>), String> {
+    let mut values = [0.0; 6];
+    let color = match parse_floats("v", args, &mut values)? {
+        3 | 4 => None,
+        6 => Some(Vector3::new(values[3], values[4], values[5])),
+        n => return Err(format!("`v` needs 3, 4 or 6 values, got {n}")),
+    };
+    Ok((Vector3::new(values[0], values[1], values[2]), color))
+}
+
+// u, u v or u v w, missing components are 0
+fn parse_texture_coord(args: &str) -> Result<Vector3<f32>, String> {
+    let mut values = [0.0; 3];
+    match parse_floats("vt", args, &mut values)? {
+        1..=3 => Ok(Vector3::from(values)),
+        n => Err(format!("`vt` needs 1 to 3 values, got {n}")),
+    }
+}
+
+fn parse_vertex_norm(args: &str) -> Result<Vector3<f32>, String> {
+    let mut values = [0.0; 3];
+    match parse_floats("vn", args, &mut values)? {
+        3 => Ok(Vector3::from(values)),
+        n => Err(format!("`vn` needs 3 values, got {n}")),
+    }
+}
+
+#[derive(Debug)]
+pub struct ModelError {
+    pub file: String,
+    // 1-based, 0 when the error is not about a single line
+    pub line: usize,
+    pub kind: io::ErrorKind,
+    pub reason: String,
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}: {}", self.file, self.reason)
+        } else {
+            write!(f, "{}:{}: {}", self.file, self.line, self.reason)
+        }
+    }
+}
+
+impl Error for ModelError {}
+
+impl From<ModelError> for io::Error {
+    fn from(err: ModelError) -> Self {
+        io::Error::new(err.kind, err)
+    }
+}
+
+fn open_error(filename: &str, err: io::Error) -> ModelError {
+    ModelError {
+        file: filename.to_string(),
+        line: 0,
+        kind: err.kind(),
+        reason: err.to_string(),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ParseMode {
+    // the first malformed line fails the load
+    Strict,
+    // malformed lines are skipped and kept in `Model::warnings`
+    Lenient,
+}
+
+// v, vt and vn indices of a face corner as written in the file
+type RawCorner = [Option<i64>; 3];
+
+enum Statement {
+    // `corners` is a range of `ObjChunk::corners`, `counts` are the v, vt and vn counts of
+    // the chunk at the face, which negative indices are relative to
+    Face {
+        corners: Range<usize>,
+        counts: [usize; 3],
+    },
+    MaterialLibrary(String),
+    UseMaterial(String),
+    Object(String),
+    Group(String),
+    SmoothingGroup(u32),
+}
+
+// what a run of lines defines, scanned without knowing the lines before it
 #[derive(Default)]
-pub struct Model {
-    vertices: Vec<Vector3<f32>>,
+struct ObjChunk {
+    positions: Vec<Vector3<f32>>,
+    colors: Vec<Vector3<f32>>,
     texture_coords: Vec<Vector3<f32>>,
     vertex_norms: Vec<Vector3<f32>>,
-    faces: Vec<[Vector3<f32>; 3]>,
+    has_vertex_colors: bool,
+    corners: Vec<RawCorner>,
+    // everything but attributes in file order, with the line number within the chunk
+    statements: Vec<(usize, Result<Statement, String>)>,
+    line_cnt: usize,
 }
 
-impl Model {
-    pub fn new(filename: &str) -> io::Result<Self> {
-        let mut model = Model::default();
-
-        for line in read_to_string(filename)?.lines() {
-            // parse vertices
-            if line.starts_with("v ") {
-                let parsed_vertex: Vec<f32> = line
-                    .split_whitespace()
-                    .filter_map(|fstr| fstr.parse::<f32>().ok())
-                    .collect();
-                if parsed_vertex.len() != 3 {
-                    continue;
-                }
-                let mut vertex = Vector3::zeros();
-                for i in 0..3 {
-                    vertex[i] = parsed_vertex[i];
+impl ObjChunk {
+    fn scan(bytes: &[u8]) -> Self {
+        let mut chunk = Self::default();
+        if bytes.is_empty() {
+            return chunk;
+        }
+        // a trailing newline ends the last line instead of starting an empty one
+        let bytes = bytes.strip_suffix(b"\n").unwrap_or(bytes);
+        for line in bytes.split(|&b| b == b'\n') {
+            match str::from_utf8(line) {
+                Ok(line) => chunk.scan_line(line),
+                // same message as `BufRead::read_line`
+                Err(_) => chunk.scan_invalid_line("stream did not contain valid UTF-8".into()),
+            }
+        }
+        chunk
+    }
+
+    fn scan_line(&mut self, line: &str) {
+        self.line_cnt += 1;
+        if let Err(reason) = self.scan_statement(line) {
+            self.statements.push((self.line_cnt, Err(reason)));
+        }
+    }
+
+    fn scan_invalid_line(&mut self, reason: String) {
+        self.line_cnt += 1;
+        self.statements.push((self.line_cnt, Err(reason)));
+    }
+
+    fn push(&mut self, statement: Statement) {
+        self.statements.push((self.line_cnt, Ok(statement)));
+    }
+
+    fn scan_statement(&mut self, line: &str) -> Result<(), String> {
+        // any statement can be followed by a comment
+        let line = line.split_once('#').map_or(line, |(line, _)| line).trim();
+        let (keyword, values) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        match keyword {
+            // malformed attributes still take up their index so later faces stay correct
+            "v" => {
+                let parsed = parse_vertex(values);
+                let (position, color) = parsed.clone().unwrap_or_default();
+                self.positions.push(position);
+                self.has_vertex_colors |= color.is_some();
+                self.colors
+                    .push(color.unwrap_or(Vector3::new(1.0, 1.0, 1.0)));
+                parsed?;
+            }
+            "vt" => {
+                let parsed = parse_texture_coord(values);
+                self.texture_coords.push(parsed.clone().unwrap_or_default());
+                parsed?;
+            }
+            "vn" => {
+                let parsed = parse_vertex_norm(values);
+                self.vertex_norms.push(parsed.clone().unwrap_or_default());
+                parsed?;
+            }
+            "f" => {
+                let start = self.corners.len();
+                if let Err(reason) = self.scan_face(values) {
+                    self.corners.truncate(start);
+                    return Err(reason);
                 }
-                model.vertices.push(vertex);
-            } else if line.starts_with("f ") {
-                // parse faces
-                let parsed_face: Vec<Vector3<f32>> = line
-                    .split_whitespace()
-                    .filter_map(|fstr| -> Option<Vector3<f32>> {
-                        let parts: Vec<usize> = fstr
-                            .split('/')
-                            .filter_map(|istr| istr.parse::<usize>().ok())
-                            .collect();
-                        if parts.len() != 3 {
-                            return None;
-                        } else {
-                            return Some(Vector3::new(
-                                (parts[0] - 1) as f32,
-                                (parts[1] - 1) as f32,
-                                (parts[2] - 1) as f32,
-                            ));
-                        }
-                    })
-                    .collect();
-                if parsed_face.len() != 3 {
-                    continue;
+                let counts = [
+                    self.positions.len(),
+                    self.texture_coords.len(),
+                    self.vertex_norms.len(),
+                ];
+                self.push(Statement::Face {
+                    corners: start..self.corners.len(),
+                    counts,
+                });
+            }
+            "mtllib" => self.push(Statement::MaterialLibrary(values.trim().to_string())),
+            "usemtl" => self.push(Statement::UseMaterial(values.trim().to_string())),
+            "o" => self.push(Statement::Object(values.trim().to_string())),
+            // names are split when applied, a face can be in several groups
+            "g" => self.push(Statement::Group(values.trim().to_string())),
+            "s" => {
+                let group = match values.trim() {
+                    "off" => 0,
+                    "on" => 1,
+                    group => group
+                        .parse::<u32>()
+                        .map_err(|_| format!("invalid smoothing group `{group}`"))?,
+                };
+                self.push(Statement::SmoothingGroup(group));
+            }
+            // comments, blank lines and statements for curves and surfaces
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // corners are v, v/vt, v//vn or v/vt/vn
+    fn scan_face(&mut self, args: &str) -> Result<(), String> {
+        for fstr in args.split_whitespace() {
+            let mut parts = fstr.split('/');
+            let vertex = parse_index(parts.next().unwrap_or(""))
+                .map_err(|reason| format!("vertex {reason}"))?;
+            if vertex.is_none() {
+                return Err(format!("face corner `{fstr}` has no vertex"));
+            }
+            let texture_coord = parse_index(parts.next().unwrap_or(""))
+                .map_err(|reason| format!("texture coord {reason}"))?;
+            let vertex_norm = parse_index(parts.next().unwrap_or(""))
+                .map_err(|reason| format!("normal {reason}"))?;
+            self.corners.push([vertex, texture_coord, vertex_norm]);
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        self.positions.clear();
+        self.colors.clear();
+        self.texture_coords.clear();
+        self.vertex_norms.clear();
+        self.has_vertex_colors = false;
+        self.corners.clear();
+        self.statements.clear();
+        self.line_cnt = 0;
+    }
+}
+
+// assembles the model from scanned chunks, which have to be applied in file order
+struct ObjBuilder<'a> {
+    model: Model,
+    filename: &'a str,
+    dir: &'a Path,
+    mode: ParseMode,
+    positions: Vec<Vector3<f32>>,
+    colors: Vec<Vector3<f32>>,
+    texture_coords: Vec<Vector3<f32>>,
+    vertex_norms: Vec<Vector3<f32>>,
+    // (v, vt, vn) of the file to the deduplicated vertex
+    vertex_lookup: HashMap<(usize, usize, usize), u32>,
+    material: Option<usize>,
+    smoothing_group: u32,
+    // lines of the chunks applied so far
+    line_cnt: usize,
+    // buffers reused across faces
+    parsed_face: Vec<(usize, Option<usize>, Option<usize>)>,
+    corners: Vec<u32>,
+}
+
+impl<'a> ObjBuilder<'a> {
+    fn new(filename: &'a str, dir: &'a Path, mode: ParseMode) -> Self {
+        Self {
+            model: Model::default(),
+            filename,
+            dir,
+            mode,
+            positions: vec![],
+            colors: vec![],
+            texture_coords: vec![],
+            vertex_norms: vec![],
+            vertex_lookup: HashMap::new(),
+            material: None,
+            smoothing_group: 0,
+            line_cnt: 0,
+            parsed_face: vec![],
+            corners: vec![],
+        }
+    }
+
+    // consumes the chunk, leaving it empty for reuse
+    fn apply(&mut self, chunk: &mut ObjChunk) -> Result<(), ModelError> {
+        let offsets = [
+            self.positions.len(),
+            self.texture_coords.len(),
+            self.vertex_norms.len(),
+        ];
+        self.positions.append(&mut chunk.positions);
+        self.colors.append(&mut chunk.colors);
+        self.texture_coords.append(&mut chunk.texture_coords);
+        self.vertex_norms.append(&mut chunk.vertex_norms);
+        self.model.has_vertex_colors |= chunk.has_vertex_colors;
+        for (line, statement) in chunk.statements.drain(..) {
+            let result = statement
+                .and_then(|statement| self.apply_statement(statement, &chunk.corners, offsets));
+            if let Err(reason) = result {
+                let err = ModelError {
+                    file: self.filename.to_string(),
+                    line: self.line_cnt + line,
+                    kind: io::ErrorKind::InvalidData,
+                    reason,
+                };
+                match self.mode {
+                    ParseMode::Strict => return Err(err),
+                    ParseMode::Lenient => self.model.warnings.push(err),
                 }
-                model
-                    .faces
-                    .push([parsed_face[0], parsed_face[1], parsed_face[2]]);
-            } else if line.starts_with("vt ") {
-                // parse texture coords
-                let parsed_texture_coord: Vec<f32> = line
-                    .split_whitespace()
-                    .filter_map(|fstr| fstr.parse::<f32>().ok())
-                    .collect();
-                if parsed_texture_coord.len() != 3 {
-                    continue;
+            }
+        }
+        self.line_cnt += chunk.line_cnt;
+        chunk.clear();
+        Ok(())
+    }
+
+    fn apply_statement(
+        &mut self,
+        statement: Statement,
+        corners: &[RawCorner],
+        offsets: [usize; 3],
+    ) -> Result<(), String> {
+        match statement {
+            Statement::Face {
+                corners: range,
+                counts,
+            } => {
+                let counts = [
+                    offsets[0] + counts[0],
+                    offsets[1] + counts[1],
+                    offsets[2] + counts[2],
+                ];
+                self.add_face(&corners[range], counts)?;
+            }
+            Statement::MaterialLibrary(mtl_files) => {
+                for mtl_file in mtl_files.split_whitespace() {
+                    let path = self.dir.join(mtl_file);
+                    self.model
+                        .material_libraries
+                        .push(path.to_string_lossy().into_owned());
+                    let materials = read_mtl_file(&path.to_string_lossy())
+                        .map_err(|err| format!("cannot read `{}`: {err}", path.display()))?;
+                    self.model.materials.extend(materials);
                 }
-                let mut texture_coord = Vector3::zeros();
-                for i in 0..3 {
-                    texture_coord[i] = parsed_texture_coord[i];
+            }
+            Statement::UseMaterial(name) => {
+                self.material = self.model.materials.iter().position(|m| m.name == name);
+                if self.material.is_none() {
+                    return Err(format!("unknown material `{name}`"));
                 }
-                model.texture_coords.push(texture_coord);
-            } else if line.starts_with("vn ") {
-                // parse vertex norms
-                let parsed_vertex_norm: Vec<f32> = line
-                    .split_whitespace()
-                    .filter_map(|fstr| fstr.parse::<f32>().ok())
-                    .collect();
-                if parsed_vertex_norm.len() != 3 {
-                    continue;
+            }
+            Statement::Object(name) => self.model.begin_object(&name),
+            // `g` without a name still ends the groups before it
+            Statement::Group(names) if names.is_empty() => self.model.begin_group(""),
+            Statement::Group(names) => self.model.begin_groups(names.split_whitespace()),
+            Statement::SmoothingGroup(group) => self.smoothing_group = group,
+        }
+        Ok(())
+    }
+
+    fn add_face(&mut self, corners: &[RawCorner], counts: [usize; 3]) -> Result<(), String> {
+        let mut info = FaceInfo {
+            has_texture_coords: true,
+            has_vertex_norms: true,
+            material: self.material,
+            smoothing_group: self.smoothing_group,
+        };
+        self.parsed_face.clear();
+        for &[vertex, texture_coord, vertex_norm] in corners {
+            let vertex = resolve_index(vertex.unwrap_or(0), counts[0])
+                .map_err(|reason| format!("vertex {reason}"))?;
+            let texture_coord = texture_coord
+                .map(|idx| resolve_index(idx, counts[1]))
+                .transpose()
+                .map_err(|reason| format!("texture coord {reason}"))?;
+            let vertex_norm = vertex_norm
+                .map(|idx| resolve_index(idx, counts[2]))
+                .transpose()
+                .map_err(|reason| format!("normal {reason}"))?;
+            info.has_texture_coords &= texture_coord.is_some();
+            info.has_vertex_norms &= vertex_norm.is_some();
+            self.parsed_face.push((vertex, texture_coord, vertex_norm));
+        }
+        if self.parsed_face.len() < 3 {
+            return Err(format!(
+                "face needs 3 corners, got {}",
+                self.parsed_face.len()
+            ));
+        }
+        self.corners.clear();
+        for &(v, vt, vn) in &self.parsed_face {
+            // missing attributes are left out of the key so they stay zero
+            let vt = vt.filter(|_| info.has_texture_coords);
+            let vn = vn.filter(|_| info.has_vertex_norms);
+            let key = (v, vt.unwrap_or(usize::MAX), vn.unwrap_or(usize::MAX));
+            let corner = *self.vertex_lookup.entry(key).or_insert_with(|| {
+                self.model.add_vertex(Vertex {
+                    position: self.positions[v],
+                    texture_coord: vt.map(|i| self.texture_coords[i]).unwrap_or_default(),
+                    normal: vn.map(|i| self.vertex_norms[i]).unwrap_or_default(),
+                    color: self.colors[v],
+                    ..Default::default()
+                })
+            });
+            self.corners.push(corner);
+        }
+        self.model.add_polygon(&self.corners, info);
+        Ok(())
+    }
+}
+
+const CACHE_MAGIC: [u8; 8] = *b"TRMESH\0\0";
+const CACHE_VERSION: u32 = 3;
+// written in native byte order, a cache from a machine of the other endianness is rejected
+const CACHE_BYTE_ORDER: u32 = 0x0102_0304;
+
+#[derive(Default, Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+struct CacheHeader {
+    magic: [u8; 8],
+    version: u32,
+    byte_order: u32,
+    has_vertex_colors: u32,
+    vertex_cnt: u32,
+    index_cnt: u32,
+    polygon_index_cnt: u32,
+    polygon_cnt: u32,
+    // bytes of the materials, objects and groups after the arrays
+    names_len: u32,
+    // FNV-1a of everything after the header
+    checksum: u64,
+}
+
+#[derive(Default, Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+struct CacheFaceInfo {
+    // bit 0 texture coords, bit 1 vertex norms
+    flags: u32,
+    // u32::MAX without material
+    material: u32,
+    smoothing_group: u32,
+}
+
+#[derive(Default, Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+struct CachePolygon {
+    corners: [u32; 2],
+    faces: [u32; 2],
+    info: CacheFaceInfo,
+}
+
+#[derive(Default, Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+struct CacheMaterial {
+    ambient: [f32; 3],
+    diffuse: [f32; 3],
+    specular: [f32; 3],
+    shininess: f32,
+    dissolve: f32,
+    illum: u32,
+    metallic: f32,
+    roughness: f32,
+}
+
+impl From<&FaceInfo> for CacheFaceInfo {
+    fn from(info: &FaceInfo) -> Self {
+        Self {
+            flags: info.has_texture_coords as u32 | (info.has_vertex_norms as u32) << 1,
+            material: info.material.map_or(u32::MAX, |m| m as u32),
+            smoothing_group: info.smoothing_group,
+        }
+    }
+}
+
+impl From<&CacheFaceInfo> for FaceInfo {
+    fn from(info: &CacheFaceInfo) -> Self {
+        Self {
+            has_texture_coords: info.flags & 1 != 0,
+            has_vertex_norms: info.flags & 2 != 0,
+            material: (info.material != u32::MAX).then_some(info.material as usize),
+            smoothing_group: info.smoothing_group,
+        }
+    }
+}
+
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+fn bad_cache(reason: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("bad mesh cache: {reason}"),
+    )
+}
+
+// strings are stored as a u32 length and the bytes, u32::MAX stands for None
+fn put_string(names: &mut Vec<u8>, string: Option<&str>) {
+    match string {
+        Some(string) => {
+            names.extend_from_slice(&(string.len() as u32).to_ne_bytes());
+            names.extend_from_slice(string.as_bytes());
+        }
+        None => names.extend_from_slice(&u32::MAX.to_ne_bytes()),
+    }
+}
+
+fn take_bytes<'a>(names: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if names.len() < len {
+        return Err(bad_cache("truncated names"));
+    }
+    let (bytes, rest) = names.split_at(len);
+    *names = rest;
+    Ok(bytes)
+}
+
+fn take_u32(names: &mut &[u8]) -> io::Result<u32> {
+    let bytes = take_bytes(names, 4)?;
+    Ok(u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn take_string(names: &mut &[u8]) -> io::Result<Option<String>> {
+    let len = take_u32(names)?;
+    if len == u32::MAX {
+        return Ok(None);
+    }
+    let bytes = take_bytes(names, len as usize)?;
+    String::from_utf8(bytes.to_vec())
+        .map(Some)
+        .map_err(|_| bad_cache("name is not UTF-8"))
+}
+
+// reads `cnt` values straight into their final buffer
+fn read_pod<T: Pod, R: Read>(reader: &mut R, cnt: usize, checksum: &mut u64) -> io::Result<Vec<T>> {
+    let mut values = vec![T::zeroed(); cnt];
+    reader.read_exact(cast_slice_mut(&mut values))?;
+    *checksum = fnv1a(*checksum, cast_slice(&values));
+    Ok(values)
+}
+
+// indexed triangle mesh, every unique position/uv/normal combination is stored once
+#[derive(Default)]
+pub struct Model {
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    face_infos: Vec<FaceInfo>,
+    polygon_indices: Vec<u32>,
+    polygons: Vec<Polygon>,
+    materials: Vec<Material>,
+    // paths of the `mtllib` files, also those that could not be read
+    material_libraries: Vec<String>,
+    objects: Vec<SubMesh>,
+    groups: Vec<SubMesh>,
+    // how many entries at the end of `groups` new faces are added to
+    open_groups: usize,
+    has_vertex_colors: bool,
+    warnings: Vec<ModelError>,
+}
+
+impl Model {
+    pub fn new(filename: &str) -> Result<Self, ModelError> {
+        Self::load(filename, ParseMode::Lenient)
+    }
+
+    pub fn load(filename: &str, mode: ParseMode) -> Result<Self, ModelError> {
+        let file = File::open(filename).map_err(|err| open_error(filename, err))?;
+        let dir = Path::new(filename).parent().unwrap_or(Path::new(""));
+        Self::parse(BufReader::new(file), filename, dir, mode)
+    }
+
+    // reads OBJ data from memory or a stream, material libraries are looked up relative
+    // to the working directory
+    pub fn from_reader<R: BufRead>(reader: R, mode: ParseMode) -> Result<Self, ModelError> {
+        Self::parse(reader, "<reader>", Path::new(""), mode)
+    }
+
+    fn parse<R: BufRead>(
+        mut reader: R,
+        filename: &str,
+        dir: &Path,
+        mode: ParseMode,
+    ) -> Result<Self, ModelError> {
+        let mut builder = ObjBuilder::new(filename, dir, mode);
+        let mut chunk = ObjChunk::default();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match reader.read_line(&mut line) {
+                Ok(0) => break,
+                Ok(_) => chunk.scan_line(&line),
+                Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+                    chunk.scan_invalid_line(err.to_string())
                 }
-                let mut vertex_norm = Vector3::zeros();
-                for i in 0..3 {
-                    vertex_norm[i] = parsed_vertex_norm[i];
+                Err(err) => {
+                    return Err(ModelError {
+                        file: filename.to_string(),
+                        line: builder.line_cnt + 1,
+                        kind: err.kind(),
+                        reason: err.to_string(),
+                    })
                 }
-                model.vertex_norms.push(vertex_norm);
             }
+            builder.apply(&mut chunk)?;
         }
-        Ok(model)
+        Ok(builder.model)
+    }
+
+    // same result as `load`, but the file is read into memory, split at line boundaries and
+    // the chunks are parsed on `threads` threads. Only assembling the model is serial.
+    pub fn load_parallel(
+        filename: &str,
+        mode: ParseMode,
+        threads: usize,
+    ) -> Result<Self, ModelError> {
+        let data = fs::read(filename).map_err(|err| open_error(filename, err))?;
+        let dir = Path::new(filename).parent().unwrap_or(Path::new(""));
+        let threads = threads.max(1);
+
+        let mut bounds = vec![0];
+        for i in 1..threads {
+            let start = bounds[i - 1];
+            let target = (data.len() * i / threads).max(start);
+            let end = data[target..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(data.len(), |p| target + p + 1);
+            bounds.push(end);
+        }
+        bounds.push(data.len());
+
+        let chunks: Vec<ObjChunk> = thread::scope(|scope| {
+            let handles: Vec<_> = bounds
+                .windows(2)
+                .map(|range| {
+                    let bytes = &data[range[0]..range[1]];
+                    scope.spawn(move || ObjChunk::scan(bytes))
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+
+        let mut builder = ObjBuilder::new(filename, dir, mode);
+        for mut chunk in chunks {
+            builder.apply(&mut chunk)?;
+        }
+        Ok(builder.model)
+    }
+
+    // lines skipped by a lenient load
+    pub fn warnings(&self) -> &[ModelError] {
+        &self.warnings
+    }
+
+    // for readers of other formats that skip malformed parts
+    pub fn add_warning(&mut self, warning: ModelError) {
+        self.warnings.push(warning);
+    }
+
+    pub fn add_vertex(&mut self, vertex: Vertex) -> u32 {
+        self.vertices.push(vertex);
+        (self.vertices.len() - 1) as u32
+    }
+
+    // adds a polygon given by vertex indices, splitting it into triangles
+    pub fn add_polygon(&mut self, corners: &[u32], info: FaceInfo) {
+        if corners.len() < 3 {
+            return;
+        }
+        let start = self.face_cnt();
+        if corners.len() == 3 {
+            self.indices.extend_from_slice(corners);
+        } else {
+            let points: Vec<Vector3<f32>> = corners
+                .iter()
+                .map(|&corner| self.vertices[corner as usize].position)
+                .collect();
+            for [a, b, c] in triangulate_polygon(&points) {
+                self.indices
+                    .extend_from_slice(&[corners[a], corners[b], corners[c]]);
+            }
+        }
+        let corner_start = self.polygon_indices.len();
+        self.polygon_indices.extend_from_slice(corners);
+        self.polygons.push(Polygon {
+            corners: corner_start..self.polygon_indices.len(),
+            faces: start..self.face_cnt(),
+            info,
+        });
+        self.face_infos.resize(self.face_cnt(), info);
+        let end = self.face_cnt();
+        if let Some(object) = self.objects.last_mut() {
+            object.faces.end = end;
+        }
+        let open = self.groups.len() - self.open_groups;
+        for group in &mut self.groups[open..] {
+            group.faces.end = end;
+        }
+    }
+
+    // faces added from now on belong to a new object, until the next one is started
+    pub fn begin_object(&mut self, name: &str) {
+        let start = self.face_cnt();
+        Self::begin_sub_mesh(&mut self.objects, name, start);
+    }
+
+    pub fn begin_group(&mut self, name: &str) {
+        self.begin_groups([name]);
+    }
+
+    // faces added from now on belong to all of the groups, like `g head eyes`
+    pub fn begin_groups<'a, I: IntoIterator<Item = &'a str>>(&mut self, names: I) {
+        let start = self.face_cnt();
+        let open = self.groups.len() - self.open_groups;
+        // groups directly followed by another `g` have no faces
+        if self.groups[open..]
+            .iter()
+            .any(|group| group.faces.is_empty())
+        {
+            self.groups.truncate(open);
+        }
+        let first = self.groups.len();
+        for name in names {
+            if !self.groups[first..].iter().any(|group| group.name == name) {
+                self.groups.push(SubMesh {
+                    name: name.to_string(),
+                    faces: start..start,
+                });
+            }
+        }
+        self.open_groups = self.groups.len() - first;
+    }
+
+    fn begin_sub_mesh(sub_meshes: &mut Vec<SubMesh>, name: &str, start: usize) {
+        // a statement directly followed by another one has no faces
+        if sub_meshes.last().is_some_and(|last| last.faces.is_empty()) {
+            sub_meshes.pop();
+        }
+        sub_meshes.push(SubMesh {
+            name: name.to_string(),
+            faces: start..start,
+        });
     }
 
     pub fn vertex_cnt(&self) -> usize {
         self.vertices.len()
     }
 
+    pub fn vertex(&self, idx: usize) -> &Vertex {
+        &self.vertices[idx]
+    }
+
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+
+    pub fn has_vertex_colors(&self) -> bool {
+        self.has_vertex_colors
+    }
+
+    pub fn set_has_vertex_colors(&mut self, has_vertex_colors: bool) {
+        self.has_vertex_colors = has_vertex_colors;
+    }
+
     pub fn face_cnt(&self) -> usize {
-        self.faces.len()
+        self.indices.len() / 3
     }
 
-    pub fn face(&self, idx: usize) -> &[Vector3<f32>; 3] {
-        &self.faces[idx]
+    // indices of the three vertices of the face
+    pub fn face(&self, idx: usize) -> [u32; 3] {
+        [
+            self.indices[idx * 3],
+            self.indices[idx * 3 + 1],
+            self.indices[idx * 3 + 2],
+        ]
     }
 
-    pub fn vertex(&self, idx: usize) -> &Vector3<f32> {
-        &self.vertices[idx]
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    pub fn face_info(&self, idx: usize) -> &FaceInfo {
+        &self.face_infos[idx]
+    }
+
+    pub fn materials(&self) -> &[Material] {
+        &self.materials
+    }
+
+    // the .mtl files the materials were read from
+    pub fn material_libraries(&self) -> &[String] {
+        &self.material_libraries
+    }
+
+    pub fn add_material(&mut self, material: Material) -> usize {
+        self.materials.push(material);
+        self.materials.len() - 1
+    }
+
+    // `o` parts, names may repeat when an object is continued later in the file
+    pub fn objects(&self) -> &[SubMesh] {
+        &self.objects
+    }
+
+    // `g` parts, names may repeat when a group is continued later in the file
+    pub fn groups(&self) -> &[SubMesh] {
+        &self.groups
+    }
+
+    pub fn polygon_cnt(&self) -> usize {
+        self.polygons.len()
+    }
+
+    // vertex indices of the corners of the original polygon, in file order
+    pub fn polygon(&self, idx: usize) -> &[u32] {
+        &self.polygon_indices[self.polygons[idx].corners.clone()]
+    }
+
+    // the triangles the polygon was split into
+    pub fn polygon_faces(&self, idx: usize) -> Range<usize> {
+        self.polygons[idx].faces.clone()
+    }
+
+    // None for a model without vertices
+    pub fn bounding_box(&self) -> Option<BoundingBox> {
+        let first = self.vertices.first()?.position;
+        Some(self.vertices.iter().fold(
+            BoundingBox {
+                min: first,
+                max: first,
+            },
+            |bounds, vertex| BoundingBox {
+                min: bounds.min.inf(&vertex.position),
+                max: bounds.max.sup(&vertex.position),
+            },
+        ))
+    }
+
+    // Ritter's approximation, within a few percent of the smallest enclosing sphere
+    pub fn bounding_sphere(&self) -> Option<BoundingSphere> {
+        let first = self.vertices.first()?.position;
+        let farthest_from = |point: Vector3<f32>| {
+            self.vertices
+                .iter()
+                .map(|vertex| vertex.position)
+                .max_by(|a, b| {
+                    (a - point)
+                        .norm_squared()
+                        .total_cmp(&(b - point).norm_squared())
+                })
+                .unwrap_or(point)
+        };
+        let a = farthest_from(first);
+        let b = farthest_from(a);
+        let mut sphere = BoundingSphere {
+            center: (a + b) / 2.0,
+            radius: (b - a).norm() / 2.0,
+        };
+        // grow the sphere just enough to take in every point outside of it
+        for vertex in &self.vertices {
+            let offset = vertex.position - sphere.center;
+            let distance = offset.norm();
+            if distance > sphere.radius {
+                let radius = (sphere.radius + distance) / 2.0;
+                sphere.center += offset * ((radius - sphere.radius) / distance);
+                sphere.radius = radius;
+            }
+        }
+        Some(sphere)
+    }
+
+    // moves the center of the bounding box to the origin and scales the longest side to
+    // fit [-1, 1], keeping the proportions
+    pub fn normalization_transform(&self) -> Matrix4<f32> {
+        let Some(bounds) = self.bounding_box() else {
+            return Matrix4::identity();
+        };
+        let extent = bounds.size().max();
+        let scale = if extent > 0.0 { 2.0 / extent } else { 1.0 };
+        Matrix4::new_scaling(scale) * Matrix4::new_translation(&-bounds.center())
+    }
+
+    // gives every face without vertex normals the normal of its polygon
+    pub fn generate_flat_normals(&mut self) {
+        let mut corner_normals: Vec<Option<Vector3<f32>>> = vec![None; self.indices.len()];
+        for idx in 0..self.polygon_cnt() {
+            if self.polygons[idx].info.has_vertex_norms {
+                continue;
+            }
+            let points: Vec<Vector3<f32>> = self
+                .polygon(idx)
+                .iter()
+                .map(|&corner| self.vertices[corner as usize].position)
+                .collect();
+            let normal = polygon_normal(&points)
+                .try_normalize(0.0)
+                .unwrap_or_default();
+            for face in self.polygon_faces(idx) {
+                corner_normals[face * 3..face * 3 + 3].fill(Some(normal));
+            }
+        }
+        self.set_corner_normals(&corner_normals);
+    }
+
+    // gives every face without vertex normals normals averaged over the faces sharing a
+    // position. Faces only smooth with faces of the same smoothing group if the file uses
+    // them, and not across edges sharper than `crease_angle` (radians).
+    pub fn generate_smooth_normals(
+        &mut self,
+        weighting: NormalWeighting,
+        crease_angle: Option<f32>,
+    ) {
+        let targets: Vec<usize> = (0..self.face_cnt())
+            .filter(|&face| !self.face_infos[face].has_vertex_norms)
+            .collect();
+        let use_groups = targets
+            .iter()
+            .any(|&face| self.face_infos[face].smoothing_group != 0);
+        let min_cos = crease_angle.map(|angle| angle.cos());
+
+        // unit face normals and the weight of each corner
+        let mut face_normals: Vec<Vector3<f32>> = vec![Vector3::zeros(); self.face_cnt()];
+        let mut corner_weights: Vec<f32> = vec![0.0; self.indices.len()];
+        // corners of the target faces at each position
+        let mut position_corners: HashMap<[u32; 3], Vec<usize>> = HashMap::new();
+        for &face in &targets {
+            let [a, b, c] = self
+                .face(face)
+                .map(|idx| self.vertices[idx as usize].position);
+            let cross = (b - a).cross(&(c - a));
+            face_normals[face] = cross.try_normalize(0.0).unwrap_or_default();
+            for (k, (p, q, r)) in [(a, b, c), (b, c, a), (c, a, b)].into_iter().enumerate() {
+                corner_weights[face * 3 + k] = match weighting {
+                    NormalWeighting::Angle => (q - p).angle(&(r - p)),
+                    NormalWeighting::Area => cross.norm() / 2.0,
+                };
+                position_corners
+                    .entry(position_key(&p))
+                    .or_default()
+                    .push(face * 3 + k);
+            }
+        }
+
+        let mut corner_normals: Vec<Option<Vector3<f32>>> = vec![None; self.indices.len()];
+        for &face in &targets {
+            let info = self.face_infos[face];
+            let normal = face_normals[face];
+            for k in 0..3 {
+                let position = self.vertices[self.indices[face * 3 + k] as usize].position;
+                let mut sum = Vector3::zeros();
+                for &corner in &position_corners[&position_key(&position)] {
+                    let other = corner / 3;
+                    if other != face {
+                        let group = self.face_infos[other].smoothing_group;
+                        if use_groups
+                            && (info.smoothing_group == 0 || group != info.smoothing_group)
+                        {
+                            continue;
+                        }
+                        if min_cos.is_some_and(|min_cos| normal.dot(&face_normals[other]) < min_cos)
+                        {
+                            continue;
+                        }
+                    }
+                    sum += face_normals[other] * corner_weights[corner];
+                }
+                corner_normals[face * 3 + k] = Some(sum.try_normalize(0.0).unwrap_or(normal));
+            }
+        }
+        self.set_corner_normals(&corner_normals);
+    }
+
+    // replaces the normals of the corners given by `corner_normals`, splitting vertices
+    // whose corners end up with different normals
+    fn set_corner_normals(&mut self, corner_normals: &[Option<Vector3<f32>>]) {
+        // a model that has all its normals keeps its vertices as they are
+        if corner_normals.iter().all(Option::is_none) {
+            return;
+        }
+        let mut lookup: HashMap<(u32, [u32; 3]), u32> = HashMap::new();
+        let mut vertices: Vec<Vertex> = Vec::with_capacity(self.vertices.len());
+        // vertices that keep their normal keep being shared
+        let mut kept: HashMap<u32, u32> = HashMap::new();
+        let old_indices = self.indices.clone();
+        for (corner, normal) in corner_normals.iter().enumerate() {
+            let idx = self.indices[corner];
+            let vertex = self.vertices[idx as usize];
+            let new_idx = match normal {
+                Some(normal) => *lookup
+                    .entry((idx, position_key(normal)))
+                    .or_insert_with(|| {
+                        vertices.push(Vertex {
+                            normal: *normal,
+                            ..vertex
+                        });
+                        (vertices.len() - 1) as u32
+                    }),
+                None => *kept.entry(idx).or_insert_with(|| {
+                    vertices.push(vertex);
+                    (vertices.len() - 1) as u32
+                }),
+            };
+            self.indices[corner] = new_idx;
+        }
+        self.remap_polygon_corners(&old_indices);
+        self.vertices = vertices;
+        for (face, info) in self.face_infos.iter_mut().enumerate() {
+            info.has_vertex_norms |= corner_normals[face * 3].is_some();
+        }
+        for polygon in &mut self.polygons {
+            polygon.info.has_vertex_norms |= corner_normals[polygon.faces.start * 3].is_some();
+        }
+    }
+
+    // polygon corners follow the first triangle corner that used the same vertex before
+    // `indices` was changed from `old_indices`
+    fn remap_polygon_corners(&mut self, old_indices: &[u32]) {
+        for polygon in &self.polygons {
+            let corners = polygon.faces.start * 3..polygon.faces.end * 3;
+            for idx in &mut self.polygon_indices[polygon.corners.clone()] {
+                if let Some(k) = corners.clone().find(|&k| old_indices[k] == *idx) {
+                    *idx = self.indices[k];
+                }
+            }
+        }
+    }
+
+    // per vertex tangent frames for tangent space normal maps. The tangent of every face is
+    // projected onto the normal of each of its corners and summed weighted by corner angle.
+    // The handedness of a face is the orientation of its uv triangle, vertices shared by
+    // faces of both orientations (mirrored uvs) are split so each side keeps its own frame.
+    // Unlike MikkTSpace, vertices are not split where the face tangents around them merely
+    // diverge, so maps baked with it can show small differences on such vertices. Faces
+    // without uvs or normals or with collapsed uvs are skipped, vertices left without a
+    // tangent get an arbitrary one perpendicular to their normal.
+    pub fn generate_tangents(&mut self) {
+        // per vertex, the tangent sums of faces with unmirrored (0) and mirrored (1) uvs
+        let mut sums: Vec<[Vector3<f32>; 2]> = vec![[Vector3::zeros(); 2]; self.vertices.len()];
+        let mut corner_sides: Vec<Option<usize>> = vec![None; self.indices.len()];
+        for face in 0..self.face_cnt() {
+            let info = self.face_infos[face];
+            if !info.has_texture_coords || !info.has_vertex_norms {
+                continue;
+            }
+            let corners = self.face(face);
+            let [a, b, c] = corners.map(|idx| self.vertices[idx as usize]);
+            let (e1, e2) = (b.position - a.position, c.position - a.position);
+            let (d1, d2) = (
+                b.texture_coord - a.texture_coord,
+                c.texture_coord - a.texture_coord,
+            );
+            // relative to the uv edges, so tightly packed atlases still count
+            let det = d1.x * d2.y - d2.x * d1.y;
+            if det.abs() <= f32::EPSILON * d1.xy().norm() * d2.xy().norm() {
+                continue;
+            }
+            let tangent = (e1 * d2.y - e2 * d1.y) / det;
+            let side = (det < 0.0) as usize;
+            let points = [a.position, b.position, c.position];
+            for (k, &idx) in corners.iter().enumerate() {
+                let normal = self.vertices[idx as usize]
+                    .normal
+                    .try_normalize(0.0)
+                    .unwrap_or_default();
+                let Some(projected) = (tangent - normal * normal.dot(&tangent)).try_normalize(0.0)
+                else {
+                    continue;
+                };
+                let p = points[k];
+                let angle = (points[(k + 1) % 3] - p).angle(&(points[(k + 2) % 3] - p));
+                sums[idx as usize][side] += projected * angle;
+                corner_sides[face * 3 + k] = Some(side);
+            }
+        }
+
+        // the mirrored side of a vertex used by both gets a copy of the vertex
+        let mut mirrored_copies: HashMap<u32, u32> = HashMap::new();
+        let old_indices = self.indices.clone();
+        for (corner, side) in corner_sides.iter().enumerate() {
+            let idx = self.indices[corner];
+            let sides = sums[idx as usize];
+            if *side != Some(1) || sides[0] == Vector3::zeros() {
+                continue;
+            }
+            self.indices[corner] = *mirrored_copies.entry(idx).or_insert_with(|| {
+                self.vertices.push(self.vertices[idx as usize]);
+                sums.push([Vector3::zeros(), sides[1]]);
+                (self.vertices.len() - 1) as u32
+            });
+        }
+        if !mirrored_copies.is_empty() {
+            self.remap_polygon_corners(&old_indices);
+        }
+
+        for (vertex, sides) in self.vertices.iter_mut().zip(sums) {
+            let side = (sides[0] == Vector3::zeros()) as usize;
+            vertex.tangent = match sides[side].try_normalize(0.0) {
+                Some(tangent) => tangent.insert_row(3, if side == 0 { 1.0 } else { -1.0 }),
+                None => match vertex.normal.try_normalize(0.0) {
+                    Some(normal) => {
+                        // the axis furthest from the normal projected onto its plane
+                        let axis = if normal.x.abs() < 0.9 {
+                            Vector3::x()
+                        } else {
+                            Vector3::y()
+                        };
+                        (axis - normal * normal.dot(&axis))
+                            .normalize()
+                            .insert_row(3, 1.0)
+                    }
+                    None => Vector4::zeros(),
+                },
+            };
+        }
+    }
+}
+
+impl Model {
+    // writes the parsed model as a binary file that `load_cache` reads without parsing,
+    // warnings of the load are not kept
+    pub fn save_cache(&self, filename: &str) -> io::Result<()> {
+        let face_infos: Vec<CacheFaceInfo> = self.face_infos.iter().map(Into::into).collect();
+        let polygons: Vec<CachePolygon> = self
+            .polygons
+            .iter()
+            .map(|polygon| CachePolygon {
+                corners: [polygon.corners.start as u32, polygon.corners.end as u32],
+                faces: [polygon.faces.start as u32, polygon.faces.end as u32],
+                info: (&polygon.info).into(),
+            })
+            .collect();
+        let mut names: Vec<u8> = vec![];
+        names.extend_from_slice(&(self.materials.len() as u32).to_ne_bytes());
+        for material in &self.materials {
+            names.extend_from_slice(bytes_of(&CacheMaterial {
+                ambient: material.ambient.into(),
+                diffuse: material.diffuse.into(),
+                specular: material.specular.into(),
+                shininess: material.shininess,
+                dissolve: material.dissolve,
+                illum: material.illum,
+                metallic: material.metallic,
+                roughness: material.roughness,
+            }));
+            put_string(&mut names, Some(&material.name));
+            for map in [
+                &material.diffuse_map,
+                &material.specular_map,
+                &material.normal_map,
+                &material.alpha_map,
+            ] {
+                put_string(&mut names, map.as_deref());
+            }
+        }
+        for sub_meshes in [&self.objects, &self.groups] {
+            names.extend_from_slice(&(sub_meshes.len() as u32).to_ne_bytes());
+            for sub_mesh in sub_meshes {
+                put_string(&mut names, Some(&sub_mesh.name));
+                names.extend_from_slice(&(sub_mesh.faces.start as u32).to_ne_bytes());
+                names.extend_from_slice(&(sub_mesh.faces.end as u32).to_ne_bytes());
+            }
+        }
+        names.extend_from_slice(&(self.material_libraries.len() as u32).to_ne_bytes());
+        for library in &self.material_libraries {
+            put_string(&mut names, Some(library));
+        }
+
+        let sections: [&[u8]; 6] = [
+            cast_slice(&self.vertices),
+            cast_slice(&self.indices),
+            cast_slice(&face_infos),
+            cast_slice(&self.polygon_indices),
+            cast_slice(&polygons),
+            &names,
+        ];
+        let header = CacheHeader {
+            magic: CACHE_MAGIC,
+            version: CACHE_VERSION,
+            byte_order: CACHE_BYTE_ORDER,
+            has_vertex_colors: self.has_vertex_colors as u32,
+            vertex_cnt: self.vertices.len() as u32,
+            index_cnt: self.indices.len() as u32,
+            polygon_index_cnt: self.polygon_indices.len() as u32,
+            polygon_cnt: self.polygons.len() as u32,
+            names_len: names.len() as u32,
+            checksum: sections
+                .iter()
+                .fold(FNV_OFFSET, |hash, bytes| fnv1a(hash, bytes)),
+        };
+        let mut out = BufWriter::new(File::create(filename)?);
+        out.write_all(bytes_of(&header))?;
+        for section in sections {
+            out.write_all(section)?;
+        }
+        out.flush()
+    }
+
+    pub fn load_cache(filename: &str) -> io::Result<Self> {
+        let file = File::open(filename)?;
+        let file_len = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+        let mut header = CacheHeader::default();
+        reader.read_exact(bytes_of_mut(&mut header))?;
+        if header.magic != CACHE_MAGIC {
+            return Err(bad_cache("not a mesh cache"));
+        }
+        if header.version != CACHE_VERSION {
+            return Err(bad_cache("unsupported version"));
+        }
+        if header.byte_order != CACHE_BYTE_ORDER {
+            return Err(bad_cache("written with a different byte order"));
+        }
+        // checked before allocating so a corrupt header cannot ask for huge buffers
+        let expected_len = size_of::<CacheHeader>() as u64
+            + header.vertex_cnt as u64 * size_of::<Vertex>() as u64
+            + header.index_cnt as u64 * 4
+            + header.index_cnt as u64 / 3 * size_of::<CacheFaceInfo>() as u64
+            + header.polygon_index_cnt as u64 * 4
+            + header.polygon_cnt as u64 * size_of::<CachePolygon>() as u64
+            + header.names_len as u64;
+        if expected_len != file_len || header.index_cnt % 3 != 0 {
+            return Err(bad_cache("sizes do not match the file"));
+        }
+
+        let mut checksum = FNV_OFFSET;
+        let vertices: Vec<Vertex> =
+            read_pod(&mut reader, header.vertex_cnt as usize, &mut checksum)?;
+        let indices: Vec<u32> = read_pod(&mut reader, header.index_cnt as usize, &mut checksum)?;
+        let face_infos: Vec<CacheFaceInfo> =
+            read_pod(&mut reader, header.index_cnt as usize / 3, &mut checksum)?;
+        let polygon_indices: Vec<u32> = read_pod(
+            &mut reader,
+            header.polygon_index_cnt as usize,
+            &mut checksum,
+        )?;
+        let polygons: Vec<CachePolygon> =
+            read_pod(&mut reader, header.polygon_cnt as usize, &mut checksum)?;
+        let names: Vec<u8> = read_pod(&mut reader, header.names_len as usize, &mut checksum)?;
+        if checksum != header.checksum {
+            return Err(bad_cache("checksum mismatch"));
+        }
+
+        let mut model = Model {
+            vertices,
+            indices,
+            face_infos: face_infos.iter().map(Into::into).collect(),
+            polygon_indices,
+            has_vertex_colors: header.has_vertex_colors != 0,
+            ..Default::default()
+        };
+        model.polygons = polygons
+            .iter()
+            .map(|polygon| Polygon {
+                corners: polygon.corners[0] as usize..polygon.corners[1] as usize,
+                faces: polygon.faces[0] as usize..polygon.faces[1] as usize,
+                info: (&polygon.info).into(),
+            })
+            .collect();
+
+        let mut names = &names[..];
+        for _ in 0..take_u32(&mut names)? {
+            let values: CacheMaterial =
+                pod_read_unaligned(take_bytes(&mut names, size_of::<CacheMaterial>())?);
+            let name = take_string(&mut names)?.unwrap_or_default();
+            let mut maps = [None, None, None, None];
+            for map in &mut maps {
+                *map = take_string(&mut names)?;
+            }
+            let [diffuse_map, specular_map, normal_map, alpha_map] = maps;
+            model.materials.push(Material {
+                name,
+                ambient: values.ambient.into(),
+                diffuse: values.diffuse.into(),
+                specular: values.specular.into(),
+                shininess: values.shininess,
+                dissolve: values.dissolve,
+                illum: values.illum,
+                metallic: values.metallic,
+                roughness: values.roughness,
+                diffuse_map,
+                specular_map,
+                normal_map,
+                alpha_map,
+            });
+        }
+        for sub_meshes in [&mut model.objects, &mut model.groups] {
+            for _ in 0..take_u32(&mut names)? {
+                let name = take_string(&mut names)?.unwrap_or_default();
+                let start = take_u32(&mut names)? as usize;
+                let end = take_u32(&mut names)? as usize;
+                sub_meshes.push(SubMesh {
+                    name,
+                    faces: start..end,
+                });
+            }
+        }
+        for _ in 0..take_u32(&mut names)? {
+            let library = take_string(&mut names)?.unwrap_or_default();
+            model.material_libraries.push(library);
+        }
+
+        // indices and ranges are trusted by rendering, so they are checked like the OBJ
+        // loader does. Polygons follow each other without gaps, as `add_polygon` adds them.
+        let face_cnt = model.face_cnt();
+        let mut corner_end = 0;
+        let mut face_end = 0;
+        let polygons_valid = model.polygons.iter().all(|polygon| {
+            let valid = polygon.corners.start == corner_end
+                && polygon.corners.len() >= 3
+                && polygon.faces.start == face_end
+                && polygon.faces.start <= polygon.faces.end;
+            corner_end = polygon.corners.end;
+            face_end = polygon.faces.end;
+            valid
+        }) && corner_end == model.polygon_indices.len()
+            && face_end == face_cnt;
+        let valid = polygons_valid
+            && model
+                .indices
+                .iter()
+                .chain(&model.polygon_indices)
+                .all(|&idx| (idx as usize) < model.vertices.len())
+            && model.objects.iter().chain(&model.groups).all(|sub_mesh| {
+                sub_mesh.faces.start <= sub_mesh.faces.end && sub_mesh.faces.end <= face_cnt
+            })
+            && model
+                .face_infos
+                .iter()
+                .chain(model.polygons.iter().map(|polygon| &polygon.info))
+                .all(|info| info.material.is_none_or(|m| m < model.materials.len()));
+        if !valid {
+            return Err(bad_cache("index out of range"));
+        }
+        Ok(model)
+    }
+
+    // loads `cache_filename` if it is newer than the OBJ, otherwise parses the OBJ and
+    // refreshes the cache
+    pub fn load_with_cache(filename: &str, cache_filename: &str) -> Result<Self, ModelError> {
+        let modified = |name: &str| fs::metadata(name).and_then(|meta| meta.modified()).ok();
+        if let (Some(obj_time), Some(cache_time)) = (modified(filename), modified(cache_filename)) {
+            if cache_time >= obj_time {
+                if let Ok(model) = Self::load_cache(cache_filename) {
+                    // edited material libraries make the cache stale as well, a library
+                    // that still does not exist cannot have changed
+                    let libraries_unchanged = model
+                        .material_libraries
+                        .iter()
+                        .all(|library| modified(library).is_none_or(|time| time <= cache_time));
+                    if libraries_unchanged {
+                        return Ok(model);
+                    }
+                }
+            }
+        }
+        let model = Self::new(filename)?;
+        // the cache is only an optimization, a read-only directory must not fail the load
+        _ = model.save_cache(cache_filename);
+        Ok(model)
+    }
+}
+
+impl Model {
+    // writes the model as OBJ with `precision` decimals, and the materials as an .mtl file
+    // of the same name next to it
+    pub fn write_obj(&self, filename: &str, precision: usize) -> io::Result<()> {
+        let mtl_filename = Path::new(filename).with_extension("mtl");
+        let mtllib = if self.materials.is_empty() {
+            None
+        } else {
+            write_mtl_file(
+                &mtl_filename.to_string_lossy(),
+                &self.unique_materials(),
+                precision,
+            )?;
+            mtl_filename.file_name().map(|name| name.to_string_lossy())
+        };
+        let mut out = BufWriter::new(File::create(filename)?);
+        self.write_obj_to(&mut out, precision, mtllib.as_deref())?;
+        out.flush()
+    }
+
+    // `usemtl` looks materials up by name, so nameless and repeated names are replaced.
+    // OBJ has no way back to no material, so polygons without one after a `usemtl` get
+    // a default material at index `materials.len()`.
+    fn unique_materials(&self) -> Vec<Material> {
+        let mut materials = self.materials.clone();
+        for i in 0..materials.len() {
+            let name = &materials[i].name;
+            let taken = materials[..i].iter().any(|other| &other.name == name);
+            // `newmtl` collapses whitespace and `usemtl` ends at a comment, such names
+            // would not be found again
+            let collapsed = name.split_whitespace().collect::<Vec<&str>>().join(" ");
+            if name.is_empty() || *name != collapsed || name.contains('#') || taken {
+                materials[i].name = format!("material{i}");
+            }
+        }
+        let first_material = self
+            .polygons
+            .iter()
+            .position(|polygon| polygon.info.material.is_some());
+        if first_material.is_some_and(|first| {
+            self.polygons[first..]
+                .iter()
+                .any(|polygon| polygon.info.material.is_none())
+        }) {
+            let mut name = "default".to_string();
+            let mut suffix = 1;
+            while materials.iter().any(|material| material.name == name) {
+                name = format!("default{suffix}");
+                suffix += 1;
+            }
+            materials.push(Material {
+                name,
+                ..Default::default()
+            });
+        }
+        materials
+    }
+
+    // the polygons of the model in OBJ form, `mtllib` is the .mtl file to reference when
+    // the materials are written separately
+    pub fn write_obj_to<W: Write>(
+        &self,
+        out: &mut W,
+        precision: usize,
+        mtllib: Option<&str>,
+    ) -> io::Result<()> {
+        // positions, uvs and normals are stored once each, faces refer to them by index
+        let mut positions: Vec<&Vertex> = vec![];
+        let mut texture_coords: Vec<Vector3<f32>> = vec![];
+        let mut normals: Vec<Vector3<f32>> = vec![];
+        let mut position_lookup: HashMap<([u32; 3], [u32; 3]), usize> = HashMap::new();
+        let mut texture_coord_lookup: HashMap<[u32; 3], usize> = HashMap::new();
+        let mut normal_lookup: HashMap<[u32; 3], usize> = HashMap::new();
+        let mut corners: Vec<[usize; 3]> = Vec::with_capacity(self.polygon_indices.len());
+        for polygon in &self.polygons {
+            for &idx in &self.polygon_indices[polygon.corners.clone()] {
+                let vertex = &self.vertices[idx as usize];
+                let color = if self.has_vertex_colors {
+                    position_key(&vertex.color)
+                } else {
+                    [0; 3]
+                };
+                let position = *position_lookup
+                    .entry((position_key(&vertex.position), color))
+                    .or_insert_with(|| {
+                        positions.push(vertex);
+                        positions.len()
+                    });
+                let texture_coord = if polygon.info.has_texture_coords {
+                    *texture_coord_lookup
+                        .entry(position_key(&vertex.texture_coord))
+                        .or_insert_with(|| {
+                            texture_coords.push(vertex.texture_coord);
+                            texture_coords.len()
+                        })
+                } else {
+                    0
+                };
+                let normal = if polygon.info.has_vertex_norms {
+                    *normal_lookup
+                        .entry(position_key(&vertex.normal))
+                        .or_insert_with(|| {
+                            normals.push(vertex.normal);
+                            normals.len()
+                        })
+                } else {
+                    0
+                };
+                corners.push([position, texture_coord, normal]);
+            }
+        }
+
+        let p = precision;
+        writeln!(out, "# written by tinyrenderer")?;
+        if let Some(mtllib) = mtllib {
+            writeln!(out, "mtllib {mtllib}")?;
+        }
+        for vertex in &positions {
+            let [x, y, z] = vertex.position.into();
+            write!(out, "v {x:.p$} {y:.p$} {z:.p$}")?;
+            if self.has_vertex_colors {
+                let [r, g, b] = vertex.color.into();
+                write!(out, " {r:.p$} {g:.p$} {b:.p$}")?;
+            }
+            writeln!(out)?;
+        }
+        for texture_coord in &texture_coords {
+            writeln!(out, "vt {:.p$} {:.p$}", texture_coord.x, texture_coord.y)?;
+        }
+        for normal in &normals {
+            writeln!(out, "vn {:.p$} {:.p$} {:.p$}", normal.x, normal.y, normal.z)?;
+        }
+
+        let names = self.unique_materials();
+        let mut objects = self.objects.iter().peekable();
+        let mut groups = self.groups.iter().peekable();
+        let mut material = None;
+        let mut smoothing_group = 0;
+        for polygon in &self.polygons {
+            // sub-meshes start at the first face of a polygon, so they open before it
+            while let Some(object) = objects.next_if(|o| o.faces.start <= polygon.faces.start) {
+                writeln!(out, "{}", format!("o {}", object.name).trim_end())?;
+            }
+            // groups that start together share the faces, so they go on one line
+            let mut line: Option<String> = None;
+            while let Some(group) = groups.next_if(|g| g.faces.start <= polygon.faces.start) {
+                let line = line.get_or_insert_with(|| "g".to_string());
+                line.push(' ');
+                line.push_str(&group.name);
+            }
+            if let Some(line) = line {
+                writeln!(out, "{}", line.trim_end())?;
+            }
+            // without a material after a `usemtl` the default one of `unique_materials`
+            let polygon_material = polygon
+                .info
+                .material
+                .or(material.map(|_| self.materials.len()));
+            if let Some(idx) = polygon_material.filter(|&idx| Some(idx) != material) {
+                material = Some(idx);
+                writeln!(out, "usemtl {}", names[idx].name)?;
+            }
+            if polygon.info.smoothing_group != smoothing_group {
+                smoothing_group = polygon.info.smoothing_group;
+                match smoothing_group {
+                    0 => writeln!(out, "s off")?,
+                    group => writeln!(out, "s {group}")?,
+                }
+            }
+            write!(out, "f")?;
+            for &[position, texture_coord, normal] in &corners[polygon.corners.clone()] {
+                match (texture_coord, normal) {
+                    (0, 0) => write!(out, " {position}")?,
+                    (_, 0) => write!(out, " {position}/{texture_coord}")?,
+                    (0, _) => write!(out, " {position}//{normal}")?,
+                    _ => write!(out, " {position}/{texture_coord}/{normal}")?,
+                }
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a file in the temp directory that is removed again when dropped
+    struct TempFile(std::path::PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, contents: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("tinyrenderer-{}-{name}", std::process::id()));
+            fs::write(&path, contents).unwrap();
+            Self(path)
+        }
+
+        fn path(&self) -> &str {
+            self.0.to_str().unwrap()
+        }
     }
 
-    pub fn texture_coord(&self, idx: usize) -> &Vector3<f32> {
-        &self.texture_coords[idx]
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            _ = fs::remove_file(&self.0);
+        }
     }
 
-    pub fn vertex_norm(&self, idx: usize) -> &Vector3<f32> {
-        &self.vertex_norms[idx]
+    fn assert_same(a: &Model, b: &Model) {
+        assert_eq!(a.vertices, b.vertices);
+        assert_eq!(a.indices, b.indices);
+        assert_eq!(a.face_infos, b.face_infos);
+        assert_eq!(a.polygon_indices, b.polygon_indices);
+        assert_eq!(a.polygons, b.polygons);
+        assert_eq!(a.objects, b.objects);
+        assert_eq!(a.groups, b.groups);
+        assert_eq!(a.has_vertex_colors, b.has_vertex_colors);
+        let names = |model: &Model| -> Vec<String> {
+            model.materials.iter().map(|m| m.name.clone()).collect()
+        };
+        assert_eq!(names(a), names(b));
+        assert_eq!(a.material_libraries, b.material_libraries);
+        let warnings = |model: &Model| -> Vec<(usize, String)> {
+            model
+                .warnings
+                .iter()
+                .map(|w| (w.line, w.reason.clone()))
+                .collect()
+        };
+        assert_eq!(warnings(a), warnings(b));
+    }
+
+    #[test]
+    fn parallel_load_matches_serial_load() {
+        let mtl = TempFile::new(
+            "parallel.mtl",
+            "newmtl red\nKd 1 0 0\nnewmtl blue\nKd 0 0 1\n",
+        );
+        let mtl_name = Path::new(mtl.path()).file_name().unwrap().to_str().unwrap();
+        let mut obj = format!("# grid with every kind of statement\r\nmtllib {mtl_name}\r\n");
+        for row in 0..12 {
+            obj += &format!("o row{row}\r\n");
+            if row % 3 == 0 {
+                obj += "g head eyes\r\n";
+            } else {
+                obj += &format!("g part{row} # comment\n");
+            }
+            obj += if row % 2 == 0 {
+                "usemtl red\n"
+            } else {
+                "usemtl blue\r\n"
+            };
+            obj += match row % 3 {
+                0 => "s off\n",
+                1 => "s on # same as 1\r\n",
+                _ => "s 2\n",
+            };
+            for col in 0..4 {
+                obj += &format!("v {col} {row} 0 0.5 0.5 0.5 # colored\r\n");
+                obj += &format!("vt {} {}\r\n", col as f32 / 4.0, row as f32 / 12.0);
+            }
+            obj += "vn 0 0 1\n";
+            // negative indices reach back into this row and the one before
+            if row > 0 {
+                obj += "f -8/-8/-1 -7/-7/-1 -3/-3/-1 -4/-4/-1\r\n";
+                obj += "f -7//-1 -6//-1 -2//-1\n";
+                obj += "f -6/-6 -5/-5 -1/-1 -2/-2\r\n";
+            }
+            obj += "f 1 2 3 # absolute\n";
+            // lenient loads skip these and keep a warning with the line number
+            obj += "f 1 2 x\r\n";
+            obj += "usemtl missing\n";
+        }
+        // no newline at the end of the file
+        obj += "f 1 2 4";
+        let file = TempFile::new("parallel.obj", &obj);
+
+        let serial = Model::load(file.path(), ParseMode::Lenient).unwrap();
+        assert!(serial.face_cnt() > 0 && !serial.warnings().is_empty());
+        // enough thread counts that chunk boundaries fall inside every kind of line
+        for threads in (1..=40).chain([97, 500, obj.len(), obj.len() * 2]) {
+            let parallel = Model::load_parallel(file.path(), ParseMode::Lenient, threads).unwrap();
+            assert_same(&serial, &parallel);
+        }
+
+        let strict_error = |result: Result<Model, ModelError>| {
+            let err = result.err().unwrap();
+            (err.line, err.reason)
+        };
+        let serial = strict_error(Model::load(file.path(), ParseMode::Strict));
+        for threads in [1, 2, 5, 16, 64] {
+            let parallel = Model::load_parallel(file.path(), ParseMode::Strict, threads);
+            assert_eq!(serial, strict_error(parallel));
+        }
+    }
+
+    #[test]
+    fn comments_end_every_statement() {
+        let mtl = TempFile::new("comments.mtl", "newmtl skin\nKd 1 0.8 0.6\n");
+        let obj = format!(
+            "mtllib {} # materials
+v 0 0 0 # origin
+v 1 0 0
+v 1 1 0
+o head # the object
+g body eyes # two groups
+usemtl skin # x
+s on # smooth
+f 1 2 3 # face
+s off
+f 1 3 2
+",
+            mtl.path()
+        );
+        let model = Model::from_reader(obj.as_bytes(), ParseMode::Strict).unwrap();
+        assert_eq!(model.material_libraries, [mtl.path()]);
+        let names = |sub_meshes: &[SubMesh]| -> Vec<String> {
+            sub_meshes.iter().map(|s| s.name.clone()).collect()
+        };
+        assert_eq!(names(&model.objects), ["head"]);
+        assert_eq!(names(&model.groups), ["body", "eyes"]);
+        assert_eq!(model.face_info(0).material, Some(0));
+        assert_eq!(model.face_info(0).smoothing_group, 1);
+        assert_eq!(model.face_info(1).smoothing_group, 0);
+    }
+
+    #[test]
+    fn smooth_normals_join_negative_zero() {
+        // a fold along the y axis, the second triangle writes its x = 0 corners as -0
+        let obj = "v 0 0 0\nv 0 1 0\nv -1 0 1\nv -0 0 0\nv 1 0 1\nv -0 1 0\nf 1 2 3\nf 4 5 6\n";
+        let mut model = Model::from_reader(obj.as_bytes(), ParseMode::Strict).unwrap();
+        model.generate_smooth_normals(NormalWeighting::Angle, None);
+        let normal = |idx: usize| model.vertex(idx).normal;
+        assert_eq!(normal(0), normal(3));
+        assert_eq!(normal(1), normal(5));
+        assert!((normal(0) - Vector3::new(0.0, 0.0, 1.0)).norm() < 1e-6);
+    }
+
+    #[test]
+    fn tangents_for_small_and_collapsed_uvs() {
+        // the first triangle maps to a tiny mirrored uv area with u along y, the second to a
+        // single uv
+        let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 2 0 0\nv 3 0 0\nv 2 1 0\n\
+            vt 0.5 0.5\nvt 0.5001 0.5\nvt 0.5 0.5001\nvn 0 0 1\n\
+            f 1/1/1 2/3/1 3/2/1\nf 4/1/1 5/1/1 6/1/1\n";
+        let mut model = Model::from_reader(obj.as_bytes(), ParseMode::Strict).unwrap();
+        model.generate_tangents();
+        for idx in 0..3 {
+            let tangent = model.vertex(idx).tangent;
+            assert!((tangent - Vector4::new(0.0, 1.0, 0.0, -1.0)).norm() < 1e-3);
+        }
+        for idx in 3..model.vertex_cnt() {
+            let vertex = model.vertex(idx);
+            assert!((vertex.tangent.xyz().norm() - 1.0).abs() < 1e-6);
+            assert!(vertex.tangent.xyz().dot(&vertex.normal).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn written_obj_loads_as_the_same_model() {
+        let mtl = TempFile::new(
+            "roundtrip.mtl",
+            "newmtl red\nKd 1 0 0\nmap_Kd tex.tga\nnewmtl blue\nKd 0 0 1\n",
+        );
+        let mtl_name = Path::new(mtl.path()).file_name().unwrap().to_str().unwrap();
+        let obj = format!(
+            "mtllib {mtl_name}
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0.5 0.5 1
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+vn 0 0.6 0.8
+f 1/1/1 2/2/1 3/3/1
+o top
+g head eyes
+usemtl red
+s 1
+f 1/1/1 3/3/1 4/4/1 5/1/2
+g eyes
+usemtl blue
+s off
+f 2 3 5
+f 3//2 4//2 5//2
+"
+        );
+        let file = TempFile::new("roundtrip.obj", &obj);
+        let mut model = Model::new(file.path()).unwrap();
+        // not possible in OBJ, the writer needs a material for it
+        let info = FaceInfo {
+            material: None,
+            ..*model.face_info(model.face_cnt() - 1)
+        };
+        let corners = model.polygon(model.polygon_cnt() - 1).to_vec();
+        model.add_polygon(&corners, info);
+        // relative to the working directory instead of the .mtl
+        model.materials[1].diffuse_map = Some("obj/african_head_diffuse.tga".to_string());
+
+        let out = TempFile::new("roundtrip-out.obj", "");
+        let out_mtl = TempFile::new("roundtrip-out.mtl", "");
+        model.write_obj(out.path(), 6).unwrap();
+        let written = Model::new(out.path()).unwrap();
+        assert!(written.warnings().is_empty());
+        assert_eq!(written.material_libraries, [out_mtl.path()]);
+
+        assert_eq!(model.vertices, written.vertices);
+        assert_eq!(model.indices, written.indices);
+        assert_eq!(model.polygon_indices, written.polygon_indices);
+        assert_eq!(model.objects, written.objects);
+        assert_eq!(model.groups, written.groups);
+        let ranges = |model: &Model| -> Vec<(Range<usize>, Range<usize>)> {
+            model
+                .polygons
+                .iter()
+                .map(|polygon| (polygon.corners.clone(), polygon.faces.clone()))
+                .collect()
+        };
+        assert_eq!(ranges(&model), ranges(&written));
+        let without_material = |model: &Model| -> Vec<FaceInfo> {
+            model
+                .face_infos
+                .iter()
+                .map(|info| FaceInfo {
+                    material: None,
+                    ..*info
+                })
+                .collect()
+        };
+        assert_eq!(without_material(&model), without_material(&written));
+        let material_names = |model: &Model| -> Vec<Option<String>> {
+            model
+                .face_infos
+                .iter()
+                .map(|info| info.material.map(|idx| model.materials[idx].name.clone()))
+                .collect()
+        };
+        let mut expected = material_names(&model);
+        *expected.last_mut().unwrap() = Some("default".to_string());
+        assert_eq!(expected, material_names(&written));
+
+        let maps = |model: &Model| -> Vec<Option<String>> {
+            model
+                .materials
+                .iter()
+                .map(|material| material.diffuse_map.clone())
+                .collect()
+        };
+        let absolute = std::path::absolute("obj/african_head_diffuse.tga").unwrap();
+        let texture = Path::new(mtl.path()).with_file_name("tex.tga");
+        assert_eq!(
+            maps(&written),
+            [
+                Some(texture.to_str().unwrap().to_string()),
+                Some(absolute.to_str().unwrap().to_string()),
+                None,
+            ]
+        );
     }
 }
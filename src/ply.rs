@@ -0,0 +1,350 @@
+use std::{fs, io, str::SplitAsciiWhitespace};
+
+use nalgebra::Vector3;
+
+use crate::model::{FaceInfo, Model, ModelError, Vertex};
+
+fn bad_ply(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("PLY: {}", reason))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy)]
+enum ScalarType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> io::Result<Self> {
+        match name {
+            "char" | "int8" => Ok(Self::Int8),
+            "uchar" | "uint8" => Ok(Self::UInt8),
+            "short" | "int16" => Ok(Self::Int16),
+            "ushort" | "uint16" => Ok(Self::UInt16),
+            "int" | "int32" => Ok(Self::Int32),
+            "uint" | "uint32" => Ok(Self::UInt32),
+            "float" | "float32" => Ok(Self::Float32),
+            "double" | "float64" => Ok(Self::Float64),
+            _ => Err(bad_ply(&format!("unknown property type `{name}`"))),
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Self::Int8 | Self::UInt8 => 1,
+            Self::Int16 | Self::UInt16 => 2,
+            Self::Int32 | Self::UInt32 | Self::Float32 => 4,
+            Self::Float64 => 8,
+        }
+    }
+
+    // colors stored as integers are 0-255, as floats 0-1
+    fn is_integer(&self) -> bool {
+        !matches!(self, Self::Float32 | Self::Float64)
+    }
+}
+
+struct Property {
+    name: String,
+    // item type, and the count type for list properties
+    scalar: ScalarType,
+    list_count: Option<ScalarType>,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+// values of the body in file order
+enum Body<'a> {
+    Ascii(SplitAsciiWhitespace<'a>),
+    Binary {
+        data: &'a [u8],
+        pos: usize,
+        big_endian: bool,
+    },
+}
+
+impl Body<'_> {
+    fn read(&mut self, scalar: ScalarType) -> io::Result<f64> {
+        match self {
+            Body::Ascii(tokens) => tokens
+                .next()
+                .ok_or_else(|| bad_ply("unexpected end of data"))?
+                .parse::<f64>()
+                .map_err(|_| bad_ply("invalid number")),
+            Body::Binary {
+                data,
+                pos,
+                big_endian,
+            } => {
+                let size = scalar.size();
+                if *pos + size > data.len() {
+                    return Err(bad_ply("unexpected end of data"));
+                }
+                let mut bytes = [0u8; 8];
+                bytes[..size].copy_from_slice(&data[*pos..*pos + size]);
+                *pos += size;
+                if *big_endian {
+                    bytes[..size].reverse();
+                }
+                Ok(match scalar {
+                    ScalarType::Int8 => bytes[0] as i8 as f64,
+                    ScalarType::UInt8 => bytes[0] as f64,
+                    ScalarType::Int16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+                    ScalarType::UInt16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+                    ScalarType::Int32 => {
+                        i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64
+                    }
+                    ScalarType::UInt32 => {
+                        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64
+                    }
+                    ScalarType::Float32 => {
+                        f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64
+                    }
+                    ScalarType::Float64 => f64::from_le_bytes(bytes),
+                })
+            }
+        }
+    }
+}
+
+fn parse_header(header: &str) -> io::Result<(PlyFormat, Vec<Element>)> {
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+    for line in header.lines().skip(1) {
+        let mut parts = line.split_whitespace();
+        match parts.next() {
+            Some("format") => {
+                format = Some(match parts.next() {
+                    Some("ascii") => PlyFormat::Ascii,
+                    Some("binary_little_endian") => PlyFormat::BinaryLittleEndian,
+                    Some("binary_big_endian") => PlyFormat::BinaryBigEndian,
+                    _ => return Err(bad_ply("unknown format")),
+                });
+            }
+            Some("element") => {
+                let (Some(name), Some(count)) = (parts.next(), parts.next()) else {
+                    return Err(bad_ply("incomplete element"));
+                };
+                elements.push(Element {
+                    name: name.to_string(),
+                    count: count
+                        .parse::<usize>()
+                        .map_err(|_| bad_ply("invalid element count"))?,
+                    properties: vec![],
+                });
+            }
+            Some("property") => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| bad_ply("property outside of an element"))?;
+                let property = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                    (Some("list"), Some(count), Some(item), Some(name)) => Property {
+                        name: name.to_string(),
+                        scalar: ScalarType::parse(item)?,
+                        list_count: Some(ScalarType::parse(count)?),
+                    },
+                    (Some(scalar), Some(name), None, None) => Property {
+                        name: name.to_string(),
+                        scalar: ScalarType::parse(scalar)?,
+                        list_count: None,
+                    },
+                    _ => return Err(bad_ply("malformed property")),
+                };
+                element.properties.push(property);
+            }
+            // comment, obj_info
+            _ => {}
+        }
+    }
+    Ok((format.ok_or_else(|| bad_ply("missing format"))?, elements))
+}
+
+pub fn read_ply_file(filename: &str) -> io::Result<Model> {
+    read_ply(&fs::read(filename)?, filename)
+}
+
+// `filename` is only used in the warnings of the model
+pub fn read_ply(data: &[u8], filename: &str) -> io::Result<Model> {
+    if !data.starts_with(b"ply") {
+        return Err(bad_ply("missing magic"));
+    }
+    let header_end = data
+        .windows(b"end_header".len())
+        .position(|window| window == b"end_header")
+        .ok_or_else(|| bad_ply("missing end_header"))?;
+    // the body starts after the line break of end_header
+    let body_start = data[header_end..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(data.len(), |p| header_end + p + 1);
+    let header =
+        std::str::from_utf8(&data[..header_end]).map_err(|_| bad_ply("header is not text"))?;
+    let (format, elements) = parse_header(header)?;
+
+    let mut body = match format {
+        PlyFormat::Ascii => Body::Ascii(
+            std::str::from_utf8(&data[body_start..])
+                .map_err(|_| bad_ply("ascii body is not text"))?
+                .split_ascii_whitespace(),
+        ),
+        _ => Body::Binary {
+            data,
+            pos: body_start,
+            big_endian: format == PlyFormat::BinaryBigEndian,
+        },
+    };
+
+    let mut model = Model::default();
+    let mut vertex_cnt = 0;
+    let mut has_texture_coords = false;
+    let mut has_vertex_norms = false;
+    let mut values: Vec<f64> = vec![];
+    let mut corners: Vec<u32> = vec![];
+    let mut face_cnt = 0;
+    for element in &elements {
+        // where the properties we understand are, by property index
+        let find = |names: &[&str]| {
+            element
+                .properties
+                .iter()
+                .position(|p| p.list_count.is_none() && names.contains(&p.name.as_str()))
+        };
+        let position = [find(&["x"]), find(&["y"]), find(&["z"])];
+        let normal = [find(&["nx"]), find(&["ny"]), find(&["nz"])];
+        let color = [
+            find(&["red", "r", "diffuse_red"]),
+            find(&["green", "g", "diffuse_green"]),
+            find(&["blue", "b", "diffuse_blue"]),
+        ];
+        let texture_coord = [
+            find(&["u", "s", "texture_u", "texture_s"]),
+            find(&["v", "t", "texture_v", "texture_t"]),
+        ];
+        let indices = element.properties.iter().position(|p| {
+            p.list_count.is_some() && (p.name == "vertex_indices" || p.name == "vertex_index")
+        });
+
+        if element.name == "vertex" {
+            has_vertex_norms = normal.iter().all(Option::is_some);
+            has_texture_coords = texture_coord.iter().all(Option::is_some);
+            if color.iter().all(Option::is_some) {
+                model.set_has_vertex_colors(true);
+            }
+        }
+        for _ in 0..element.count {
+            values.clear();
+            corners.clear();
+            for (i, property) in element.properties.iter().enumerate() {
+                match property.list_count {
+                    Some(count_type) => {
+                        let count = body.read(count_type)? as usize;
+                        for _ in 0..count {
+                            let value = body.read(property.scalar)?;
+                            if Some(i) == indices {
+                                if value < 0.0 || value >= vertex_cnt as f64 {
+                                    return Err(bad_ply("face index out of range"));
+                                }
+                                corners.push(value as u32);
+                            }
+                        }
+                        values.push(0.0);
+                    }
+                    None => values.push(body.read(property.scalar)?),
+                }
+            }
+            let get = |idx: Option<usize>| idx.map_or(0.0, |idx| values[idx] as f32);
+            if element.name == "vertex" {
+                let mut vertex = Vertex {
+                    position: Vector3::new(get(position[0]), get(position[1]), get(position[2])),
+                    normal: Vector3::new(get(normal[0]), get(normal[1]), get(normal[2])),
+                    texture_coord: Vector3::new(get(texture_coord[0]), get(texture_coord[1]), 0.0),
+                    color: Vector3::new(1.0, 1.0, 1.0),
+                    ..Default::default()
+                };
+                if let [Some(r), Some(g), Some(b)] = color {
+                    vertex.color = Vector3::new(get(Some(r)), get(Some(g)), get(Some(b)));
+                    if element.properties[r].scalar.is_integer() {
+                        vertex.color /= 255.0;
+                    }
+                }
+                model.add_vertex(vertex);
+                vertex_cnt += 1;
+            } else if element.name == "face" && indices.is_some() {
+                face_cnt += 1;
+                if corners.len() < 3 {
+                    model.add_warning(ModelError {
+                        file: filename.to_string(),
+                        line: 0,
+                        kind: io::ErrorKind::InvalidData,
+                        reason: format!("face {face_cnt} needs 3 corners, got {}", corners.len()),
+                    });
+                    continue;
+                }
+                model.add_polygon(
+                    &corners,
+                    FaceInfo {
+                        has_texture_coords,
+                        has_vertex_norms,
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+    }
+    Ok(model)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_vertex_colors_are_kept() {
+        let ply = "ply
+format ascii 1.0
+element vertex 3
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0
+1 0 0 0 255 0
+0 1 0 0 0 51
+3 0 1 2
+";
+        let model = read_ply(ply.as_bytes(), "colors.ply").unwrap();
+        assert!(model.has_vertex_colors());
+        assert_eq!(model.face_cnt(), 1);
+        let colors: Vec<Vector3<f32>> = (0..3).map(|idx| model.vertex(idx).color).collect();
+        assert_eq!(
+            colors,
+            [
+                Vector3::new(1.0, 0.0, 0.0),
+                Vector3::new(0.0, 1.0, 0.0),
+                Vector3::new(0.0, 0.0, 0.2),
+            ]
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+};
+
+use nalgebra::Vector3;
+
+use crate::model::{position_key, FaceInfo, Model, ModelError, NormalWeighting, Vertex};
+
+// facets meeting at a sharper angle keep separate normals, e.g. the edges of a box
+const CREASE_ANGLE_DEGREES: f32 = 30.0;
+
+fn bad_stl(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("STL: {}", reason))
+}
+
+fn f32_at(data: &[u8], offset: usize) -> f32 {
+    f32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+fn read_binary_triangles(data: &[u8]) -> Vec<[Vector3<f32>; 3]> {
+    // 80 byte header, triangle count, then 50 bytes per triangle: normal, 3 vertices and
+    // an attribute word
+    data[84..]
+        .chunks_exact(50)
+        .map(|facet| {
+            let vertex = |i: usize| {
+                let offset = 12 + i * 12;
+                Vector3::new(
+                    f32_at(facet, offset),
+                    f32_at(facet, offset + 4),
+                    f32_at(facet, offset + 8),
+                )
+            };
+            [vertex(0), vertex(1), vertex(2)]
+        })
+        .collect()
+}
+
+fn read_ascii_triangles(text: &str) -> io::Result<Vec<[Vector3<f32>; 3]>> {
+    let mut triangles = vec![];
+    let mut facet: Vec<Vector3<f32>> = vec![];
+    for line in text.lines() {
+        let mut parts = line.split_whitespace();
+        match parts.next() {
+            Some("vertex") => {
+                let mut vertex = Vector3::zeros();
+                for i in 0..3 {
+                    vertex[i] = parts
+                        .next()
+                        .and_then(|fstr| fstr.parse::<f32>().ok())
+                        .ok_or_else(|| bad_stl("invalid vertex"))?;
+                }
+                facet.push(vertex);
+            }
+            Some("endfacet") => {
+                // facets are triangles, anything else is fanned
+                for i in 1..facet.len().saturating_sub(1) {
+                    triangles.push([facet[0], facet[i], facet[i + 1]]);
+                }
+                facet.clear();
+            }
+            _ => {}
+        }
+    }
+    Ok(triangles)
+}
+
+pub fn read_stl_file(filename: &str) -> io::Result<Model> {
+    read_stl(&fs::read(filename)?, filename)
+}
+
+// `filename` is only used in the warnings of the model
+pub fn read_stl(data: &[u8], filename: &str) -> io::Result<Model> {
+    // binary files may start with "solid" too, so the size decides
+    let binary_size = (data.len() >= 84).then(|| {
+        let count = u32::from_le_bytes([data[80], data[81], data[82], data[83]]) as u64;
+        84 + count * 50
+    });
+    // text has no NUL bytes, while the triangle count and the floats of binary files
+    // nearly always do
+    let text = std::str::from_utf8(data)
+        .ok()
+        .filter(|text| text.starts_with("solid") && !text.contains('\0'));
+    let triangles = if binary_size == Some(data.len() as u64) {
+        read_binary_triangles(data)
+    } else if let Some(text) = text {
+        read_ascii_triangles(text)?
+    } else if let Some(size) = binary_size {
+        return Err(bad_stl(&format!(
+            "binary file should be {size} bytes for its triangle count, got {}",
+            data.len()
+        )));
+    } else {
+        return Err(bad_stl("neither binary nor ascii"));
+    };
+
+    // STL repeats the vertices of every facet, equal positions are welded into one vertex
+    let mut model = Model::default();
+    let mut lookup: HashMap<[u32; 3], u32> = HashMap::new();
+    let mut degenerate_cnt = 0;
+    for triangle in &triangles {
+        let corners = triangle.map(|position| {
+            *lookup.entry(position_key(&position)).or_insert_with(|| {
+                model.add_vertex(Vertex {
+                    position,
+                    color: Vector3::new(1.0, 1.0, 1.0),
+                    ..Default::default()
+                })
+            })
+        });
+        if corners[0] == corners[1] || corners[1] == corners[2] || corners[2] == corners[0] {
+            degenerate_cnt += 1;
+            continue;
+        }
+        model.add_polygon(&corners, FaceInfo::default());
+    }
+    if degenerate_cnt > 0 {
+        model.add_warning(ModelError {
+            file: filename.to_string(),
+            line: 0,
+            kind: io::ErrorKind::InvalidData,
+            reason: format!("skipped {degenerate_cnt} facets with repeated corners"),
+        });
+    }
+    // the facet normals of the file are often missing or wrong, so they are derived
+    model.generate_smooth_normals(
+        NormalWeighting::Angle,
+        Some(CREASE_ANGLE_DEGREES.to_radians()),
+    );
+    Ok(model)
+}
+
+pub fn write_stl_file(filename: &str, model: &Model) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(filename)?);
+    write_stl(&mut out, model)?;
+    out.flush()
+}
+
+// binary STL with one facet per triangle of the model
+pub fn write_stl<W: Write>(out: &mut W, model: &Model) -> io::Result<()> {
+    let mut header = [0u8; 80];
+    let title = b"binary STL written by tinyrenderer";
+    header[..title.len()].copy_from_slice(title);
+    out.write_all(&header)?;
+    out.write_all(&(model.face_cnt() as u32).to_le_bytes())?;
+    for i in 0..model.face_cnt() {
+        let [a, b, c] = model.face(i).map(|idx| model.vertex(idx as usize).position);
+        let normal = (b - a)
+            .cross(&(c - a))
+            .try_normalize(0.0)
+            .unwrap_or_default();
+        for vector in [normal, a, b, c] {
+            for value in vector.iter() {
+                out.write_all(&value.to_le_bytes())?;
+            }
+        }
+        out.write_all(&0u16.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn degenerate_facets_are_skipped_with_a_warning() {
+        // the second facet repeats its first corner as -0.0
+        let facets = [
+            [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+            [[0.0, 0.0, 0.0], [-0.0, 0.0, 0.0], [1.0, 1.0, 0.0]],
+        ];
+        let mut data = vec![0u8; 80];
+        data.extend_from_slice(&(facets.len() as u32).to_le_bytes());
+        for facet in facets {
+            // zero normal, the corners and the attribute word
+            for value in [[0.0f32; 3]].iter().chain(&facet).flatten() {
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+            data.extend_from_slice(&[0, 0]);
+        }
+        let model = read_stl(&data, "degenerate.stl").unwrap();
+        assert_eq!(model.face_cnt(), 1);
+        let warnings: Vec<String> = model.warnings().iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            warnings,
+            ["degenerate.stl: skipped 1 facets with repeated corners"]
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    env,
+    fmt::Write as _,
+    io::{self, Write},
+};
+
+use crate::tga::{TGAColor, TGAImage};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TerminalMode {
+    // 24-bit colors with two pixels per character cell
+    TrueColor,
+    // nearest xterm 256-color palette entry with two pixels per character cell
+    Ansi256,
+    // plain characters chosen by luminance
+    Ascii,
+}
+
+pub struct TerminalTarget {
+    mode: TerminalMode,
+    columns: usize,
+}
+
+const ASCII_RAMP: &[u8] = b" .:-=+*#%@";
+
+fn luminance(color: &TGAColor) -> f32 {
+    0.2126 * color.r as f32 + 0.7152 * color.g as f32 + 0.0722 * color.b as f32
+}
+
+fn cube_level(value: u8) -> usize {
+    // xterm cube levels are 0, 95, 135, 175, 215, 255
+    if value < 48 {
+        0
+    } else if value < 115 {
+        1
+    } else {
+        (value as usize - 35) / 40
+    }
+}
+
+fn ansi256(color: &TGAColor) -> u8 {
+    let levels = [0, 95, 135, 175, 215, 255];
+    let (r, g, b) = (
+        cube_level(color.r),
+        cube_level(color.g),
+        cube_level(color.b),
+    );
+    let cube_color = (levels[r], levels[g], levels[b]);
+    let average = (color.r as usize + color.g as usize + color.b as usize) / 3;
+    let gray_idx = (average.saturating_sub(3) / 10).min(23);
+    let gray = 8 + gray_idx * 10;
+
+    let dist = |(cr, cg, cb): (usize, usize, usize)| {
+        let dr = cr as i32 - color.r as i32;
+        let dg = cg as i32 - color.g as i32;
+        let db = cb as i32 - color.b as i32;
+        dr * dr + dg * dg + db * db
+    };
+    if dist((gray, gray, gray)) < dist(cube_color) {
+        (232 + gray_idx) as u8
+    } else {
+        (16 + 36 * r + 6 * g + b) as u8
+    }
+}
+
+impl TerminalTarget {
+    pub fn new(mode: TerminalMode, columns: usize) -> Self {
+        Self {
+            mode,
+            columns: columns.max(1),
+        }
+    }
+
+    // picks the mode from COLORTERM / TERM and the width from COLUMNS
+    pub fn detect() -> Self {
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        let term = env::var("TERM").unwrap_or_default();
+        let mode = if colorterm == "truecolor" || colorterm == "24bit" {
+            TerminalMode::TrueColor
+        } else if term.contains("256color") {
+            TerminalMode::Ansi256
+        } else {
+            TerminalMode::Ascii
+        };
+        let columns = env::var("COLUMNS")
+            .ok()
+            .and_then(|c| c.parse::<usize>().ok())
+            .unwrap_or(80);
+        Self::new(mode, columns)
+    }
+
+    // box filters the image down to `columns` x `rows` cells worth of pixels
+    fn downscale(&self, image: &TGAImage) -> (Vec<TGAColor>, usize, usize) {
+        let width = self.columns.min(image.get_width()).max(1);
+        // character cells are about twice as tall as wide, each cell shows two pixel rows
+        let height = (image.get_height() * width / image.get_width().max(1)).max(2) & !1;
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let ys = y * image.get_height() / height;
+            let ye = ((y + 1) * image.get_height() / height).max(ys + 1);
+            for x in 0..width {
+                let xs = x * image.get_width() / width;
+                let xe = ((x + 1) * image.get_width() / width).max(xs + 1);
+                let mut sum = [0usize; 3];
+                for sy in ys..ye {
+                    for sx in xs..xe {
+                        let c = image.get(sx, sy);
+                        sum[0] += c.r as usize;
+                        sum[1] += c.g as usize;
+                        sum[2] += c.b as usize;
+                    }
+                }
+                let n = (ye - ys) * (xe - xs);
+                pixels.push(TGAColor {
+                    r: (sum[0] / n) as u8,
+                    g: (sum[1] / n) as u8,
+                    b: (sum[2] / n) as u8,
+                    a: 255,
+                });
+            }
+        }
+        (pixels, width, height)
+    }
+
+    // converts a top row first image into escape sequences
+    pub fn render(&self, image: &TGAImage) -> String {
+        let mut out = String::new();
+        if image.get_width() == 0 || image.get_height() == 0 {
+            return out;
+        }
+        let (pixels, width, height) = self.downscale(image);
+        for y in (0..height).step_by(2) {
+            for x in 0..width {
+                let upper = &pixels[x + y * width];
+                let lower = &pixels[x + (y + 1) * width];
+                match self.mode {
+                    TerminalMode::TrueColor => {
+                        _ = write!(
+                            out,
+                            "\x1b[38;2;{};{};{};48;2;{};{};{}m\u{2580}",
+                            upper.r, upper.g, upper.b, lower.r, lower.g, lower.b
+                        );
+                    }
+                    TerminalMode::Ansi256 => {
+                        _ = write!(
+                            out,
+                            "\x1b[38;5;{};48;5;{}m\u{2580}",
+                            ansi256(upper),
+                            ansi256(lower)
+                        );
+                    }
+                    TerminalMode::Ascii => {
+                        let l = (luminance(upper) + luminance(lower)) / 2.0;
+                        let idx = (l / 256.0 * ASCII_RAMP.len() as f32) as usize;
+                        out.push(ASCII_RAMP[idx.min(ASCII_RAMP.len() - 1)] as char);
+                    }
+                }
+            }
+            if self.mode != TerminalMode::Ascii {
+                out.push_str("\x1b[0m");
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    pub fn print(&self, image: &TGAImage) -> io::Result<()> {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(self.render(image).as_bytes())?;
+        stdout.flush()
+    }
+}
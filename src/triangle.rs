@@ -51,7 +51,7 @@ pub fn draw_triangle(
     image: &mut TGAImage,
     zbuffer: &mut Vec<f32>,
     texture: &TGAImage,
-    color: &TGAColor,
+    colors: &[TGAColor; 3],
     use_texture: bool,
 ) {
     let va = vertices.column(0);
@@ -106,7 +106,20 @@ pub fn draw_triangle(
                         (texture_coord.y * texture.get_height() as f32) as usize,
                     )
                 } else {
-                    color.to_owned()
+                    // corner colors, interpolated like the normals
+                    let channel = |get: fn(&TGAColor) -> u8| {
+                        (get(&colors[0]) as f32 * w
+                            + get(&colors[2]) as f32 * u
+                            + get(&colors[1]) as f32 * v)
+                            .round()
+                            .clamp(0.0, 255.0) as u8
+                    };
+                    TGAColor {
+                        r: channel(|c| c.r),
+                        g: channel(|c| c.g),
+                        b: channel(|c| c.b),
+                        a: channel(|c| c.a),
+                    }
                 }
                 .get_color(intensity);
                 image.set(x as usize, y as usize, color);
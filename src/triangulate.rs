@@ -0,0 +1,103 @@
+use nalgebra::{Vector2, Vector3};
+
+// polygon normal by Newell's method, robust for non-planar polygons
+pub fn polygon_normal(points: &[Vector3<f32>]) -> Vector3<f32> {
+    let mut normal = Vector3::zeros();
+    for i in 0..points.len() {
+        let a = points[i];
+        let b = points[(i + 1) % points.len()];
+        normal.x += (a.y - b.y) * (a.z + b.z);
+        normal.y += (a.z - b.z) * (a.x + b.x);
+        normal.z += (a.x - b.x) * (a.y + b.y);
+    }
+    normal
+}
+
+fn cross(o: &Vector2<f32>, a: &Vector2<f32>, b: &Vector2<f32>) -> f32 {
+    (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x)
+}
+
+fn inside_triangle(p: &Vector2<f32>, a: &Vector2<f32>, b: &Vector2<f32>, c: &Vector2<f32>) -> bool {
+    let d1 = cross(a, b, p);
+    let d2 = cross(b, c, p);
+    let d3 = cross(c, a, p);
+    let has_neg = d1 < 0.0 || d2 < 0.0 || d3 < 0.0;
+    let has_pos = d1 > 0.0 || d2 > 0.0 || d3 > 0.0;
+    !(has_neg && has_pos)
+}
+
+fn fan(count: usize) -> Vec<[usize; 3]> {
+    (1..count - 1).map(|i| [0, i, i + 1]).collect()
+}
+
+// splits a polygon into triangles keeping its winding, returns indices into `points`.
+// Convex polygons are fanned, others are ear clipped on their best fitting plane.
+pub fn triangulate_polygon(points: &[Vector3<f32>]) -> Vec<[usize; 3]> {
+    if points.len() < 3 {
+        return vec![];
+    }
+    if points.len() == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    let normal = polygon_normal(points);
+    let axis = normal.iamax();
+    if normal[axis].abs() <= f32::EPSILON {
+        return fan(points.len());
+    }
+    // drop the dominant axis of the normal and keep the other two
+    let (u, v) = match axis {
+        0 => (1, 2),
+        1 => (2, 0),
+        _ => (0, 1),
+    };
+    let projected: Vec<Vector2<f32>> = points.iter().map(|p| Vector2::new(p[u], p[v])).collect();
+    // orientation of the projected polygon, convex corners turn the same way
+    let orientation = normal[axis].signum();
+
+    let n = projected.len();
+    let convex = (0..n).all(|i| {
+        cross(
+            &projected[(i + n - 1) % n],
+            &projected[i],
+            &projected[(i + 1) % n],
+        ) * orientation
+            >= 0.0
+    });
+    if convex {
+        return fan(n);
+    }
+
+    let mut remaining: Vec<usize> = (0..n).collect();
+    let mut triangles = Vec::with_capacity(n - 2);
+    while remaining.len() > 3 {
+        let m = remaining.len();
+        let ear = (0..m).find(|&i| {
+            let prev = remaining[(i + m - 1) % m];
+            let curr = remaining[i];
+            let next = remaining[(i + 1) % m];
+            let (a, b, c) = (&projected[prev], &projected[curr], &projected[next]);
+            if cross(a, b, c) * orientation <= 0.0 {
+                return false;
+            }
+            remaining
+                .iter()
+                .filter(|&&k| k != prev && k != curr && k != next)
+                .all(|&k| !inside_triangle(&projected[k], a, b, c))
+        });
+        let Some(i) = ear else {
+            // self intersecting polygon, fan whatever is left
+            break;
+        };
+        triangles.push([
+            remaining[(i + m - 1) % m],
+            remaining[i],
+            remaining[(i + 1) % m],
+        ]);
+        remaining.remove(i);
+    }
+    for i in 1..remaining.len() - 1 {
+        triangles.push([remaining[0], remaining[i], remaining[i + 1]]);
+    }
+    triangles
+}
@@ -0,0 +1,165 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+use crate::tga::TGAImage;
+
+// destination for the frames of an animation, fed top row first
+pub trait FrameSink {
+    fn write_frame(&mut self, image: &TGAImage) -> io::Result<()>;
+
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// writes every frame as its own numbered TGA file, e.g. `frame_0007.tga`
+pub struct TGASequenceSink {
+    prefix: String,
+    rle: bool,
+    frame: usize,
+}
+
+impl TGASequenceSink {
+    pub fn new(prefix: &str, rle: bool) -> Self {
+        Self {
+            prefix: prefix.to_owned(),
+            rle,
+            frame: 0,
+        }
+    }
+}
+
+impl FrameSink for TGASequenceSink {
+    fn write_frame(&mut self, image: &TGAImage) -> io::Result<()> {
+        let filename = format!("{}_{:04}.tga", self.prefix, self.frame);
+        image.write_tga_file(&filename, self.rle)?;
+        self.frame += 1;
+        Ok(())
+    }
+}
+
+// YUV4MPEG2 stream with BT.601 limited range 4:2:0 chroma, e.g. for `ffmpeg -i out.y4m`
+pub struct Y4MSink<W: Write> {
+    out: W,
+    fps_num: u32,
+    fps_den: u32,
+    size: Option<(usize, usize)>,
+    y_plane: Vec<u8>,
+    u_plane: Vec<u8>,
+    v_plane: Vec<u8>,
+}
+
+impl Y4MSink<BufWriter<File>> {
+    pub fn create(filename: &str, fps_num: u32, fps_den: u32) -> io::Result<Self> {
+        Ok(Self::new(
+            BufWriter::new(File::create(filename)?),
+            fps_num,
+            fps_den,
+        ))
+    }
+}
+
+impl Y4MSink<BufWriter<io::Stdout>> {
+    // for piping into `ffmpeg -i - ...`
+    pub fn stdout(fps_num: u32, fps_den: u32) -> Self {
+        Self::new(BufWriter::new(io::stdout()), fps_num, fps_den)
+    }
+}
+
+fn to_y(r: f32, g: f32, b: f32) -> f32 {
+    16.0 + (65.738 * r + 129.057 * g + 25.064 * b) / 256.0
+}
+
+fn to_u(r: f32, g: f32, b: f32) -> f32 {
+    128.0 + (-37.945 * r - 74.494 * g + 112.439 * b) / 256.0
+}
+
+fn to_v(r: f32, g: f32, b: f32) -> f32 {
+    128.0 + (112.439 * r - 94.154 * g - 18.285 * b) / 256.0
+}
+
+fn to_byte(value: f32) -> u8 {
+    value.round().clamp(0.0, 255.0) as u8
+}
+
+impl<W: Write> Y4MSink<W> {
+    pub fn new(out: W, fps_num: u32, fps_den: u32) -> Self {
+        Self {
+            out,
+            fps_num,
+            fps_den,
+            size: None,
+            y_plane: vec![],
+            u_plane: vec![],
+            v_plane: vec![],
+        }
+    }
+
+    fn convert(&mut self, image: &TGAImage) {
+        let width = image.get_width();
+        let height = image.get_height();
+        let cw = width.div_ceil(2);
+        let ch = height.div_ceil(2);
+        self.y_plane.resize(width * height, 0);
+        self.u_plane.resize(cw * ch, 0);
+        self.v_plane.resize(cw * ch, 0);
+
+        for y in 0..height {
+            for x in 0..width {
+                let c = image.get(x, y);
+                self.y_plane[x + y * width] = to_byte(to_y(c.r as f32, c.g as f32, c.b as f32));
+            }
+        }
+        // chroma is the average of each 2x2 block, edge pixels are repeated for odd sizes
+        for cy in 0..ch {
+            for cx in 0..cw {
+                let (mut r, mut g, mut b) = (0.0, 0.0, 0.0);
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let c = image.get((cx * 2 + dx).min(width - 1), (cy * 2 + dy).min(height - 1));
+                    r += c.r as f32 / 4.0;
+                    g += c.g as f32 / 4.0;
+                    b += c.b as f32 / 4.0;
+                }
+                self.u_plane[cx + cy * cw] = to_byte(to_u(r, g, b));
+                self.v_plane[cx + cy * cw] = to_byte(to_v(r, g, b));
+            }
+        }
+    }
+}
+
+impl<W: Write> FrameSink for Y4MSink<W> {
+    fn write_frame(&mut self, image: &TGAImage) -> io::Result<()> {
+        let size = (image.get_width(), image.get_height());
+        if size.0 == 0 || size.1 == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Empty frame"));
+        }
+        match self.size {
+            None => {
+                writeln!(
+                    self.out,
+                    "YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 C420jpeg XCOLORRANGE=LIMITED",
+                    size.0, size.1, self.fps_num, self.fps_den
+                )?;
+                self.size = Some(size);
+            }
+            Some(stream_size) if stream_size != size => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Frame size differs from the stream size",
+                ));
+            }
+            Some(_) => {}
+        }
+        self.convert(image);
+        self.out.write_all(b"FRAME\n")?;
+        self.out.write_all(&self.y_plane)?;
+        self.out.write_all(&self.u_plane)?;
+        self.out.write_all(&self.v_plane)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}